@@ -1,6 +1,14 @@
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::char::ParseCharError;
 use std::fmt::{Display, Formatter};
+use std::ops::Add;
+use std::str::FromStr;
+
+pub const MAX_SHORTEST_PATHS: usize = 10_000;
+
+const EDGE_INDEX_THRESHOLD: usize = 32;
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct VertexId(pub char);
@@ -17,6 +25,14 @@ impl PartialOrd for VertexId {
     }
 }
 
+impl FromStr for VertexId {
+    type Err = ParseCharError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<char>().map(VertexId)
+    }
+}
+
 impl Display for VertexId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -47,6 +63,59 @@ pub struct Edge {
     pub weight: u32
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    VertexNotFound(VertexId),
+    EdgeNotFound { from: VertexId, to: VertexId },
+    KeyMismatch { key: VertexId, id: VertexId },
+    ConflictingEdge { from: VertexId, to: VertexId },
+    NegativeWeight { from: VertexId, to: VertexId },
+    NegativeCycle,
+    NotADag,
+    BlockedEndpoint(VertexId),
+    Parse { line: usize, message: String }
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::VertexNotFound(id) => write!(f, "vertex {} not found", id),
+            GraphError::EdgeNotFound { from, to } => write!(f, "edge {} -> {} not found", from, to),
+            GraphError::KeyMismatch { key, id } => write!(f, "vertex {} is stored under key {}", id, key),
+            GraphError::ConflictingEdge { from, to } => write!(f, "edge {} -> {} is given conflicting weights", from, to),
+            GraphError::NegativeWeight { from, to } => write!(f, "edge {} -> {} has a negative weight", from, to),
+            GraphError::NegativeCycle => write!(f, "graph contains a negative-weight cycle"),
+            GraphError::NotADag => write!(f, "graph contains a cycle"),
+            GraphError::BlockedEndpoint(id) => write!(f, "endpoint {} is blocked", id),
+            GraphError::Parse { line, message } => write!(f, "parse error on line {}: {}", line, message)
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    Empty,
+    VertexNotFound(VertexId),
+    MissingEdge { from: VertexId, to: VertexId },
+    Suboptimal { claimed: u32, optimal: u32 }
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::Empty => write!(f, "path is empty"),
+            PathError::VertexNotFound(id) => write!(f, "vertex {} not found", id),
+            PathError::MissingEdge { from, to } => write!(f, "path uses missing edge {} -> {}", from, to),
+            PathError::Suboptimal { claimed, optimal } => write!(f, "path costs {} but the shortest costs {}", claimed, optimal)
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Graph {
     pub vertices: HashMap<VertexId, Vertex>
 }
@@ -64,96 +133,4372 @@ impl Graph {
         self.vertices.insert(id, copy);
     }
 
-    pub fn dijkstra_heap(&mut self, start: Vertex) {
-        let mut distances: HashMap<VertexId, u32> = HashMap::new();
+    pub fn add_edge(&mut self, from: VertexId, to: VertexId, weight: u32) {
+        self.vertices.entry(to).or_insert_with(|| Vertex { id: to, edges: Vec::new() });
+        self.vertices
+            .entry(from)
+            .or_insert_with(|| Vertex { id: from, edges: Vec::new() })
+            .edges
+            .push(Edge { to, weight });
+    }
+
+    /// Captures the current state so speculative edits can be undone with `restore`.
+    pub fn snapshot(&self) -> GraphSnapshot {
+        GraphSnapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: GraphSnapshot) {
+        *self = snapshot.0;
+    }
+
+    /// Removes `id` along with every edge pointing at it.
+    pub fn remove_vertex(&mut self, id: VertexId) -> Option<Vertex> {
+        let removed = self.vertices.remove(&id)?;
+
+        for vertex in self.vertices.values_mut() {
+            vertex.edges.retain(|e| e.to != id);
+        }
+
+        Some(removed)
+    }
+
+    /// Removes `id`, first adding a shortcut `u -> v` of weight `w1 + w2` for
+    /// every `u -> id` (`w1`) and `id -> v` (`w2`) unless a `u -> v` edge at
+    /// least as cheap already exists. Distances between the remaining vertices
+    /// are preserved.
+    pub fn contract_vertex(&mut self, id: VertexId) -> Result<(), GraphError> {
+        let outgoing: Vec<Edge> = match self.vertices.get(&id) {
+            Some(vertex) => vertex.edges.iter().filter(|e| e.to != id).copied().collect(),
+            None => return Err(GraphError::VertexNotFound(id)),
+        };
+
+        let incoming: Vec<(VertexId, u32)> = self.vertices
+            .values()
+            .filter(|v| v.id != id)
+            .filter_map(|v| v.edges.iter().filter(|e| e.to == id).map(|e| e.weight).min().map(|w| (v.id, w)))
+            .collect();
+
+        for (from, first) in incoming {
+            for out in &outgoing {
+                if out.to == from {
+                    continue;
+                }
+
+                let weight = first + out.weight;
+                let edges = &mut self.vertices.get_mut(&from).unwrap().edges;
+
+                match edges.iter_mut().filter(|e| e.to == out.to).min_by_key(|e| e.weight) {
+                    Some(existing) => existing.weight = existing.weight.min(weight),
+                    None => edges.push(Edge { to: out.to, weight }),
+                }
+            }
+        }
+
+        self.remove_vertex(id);
+        Ok(())
+    }
+
+    /// Removes every `from -> to` edge.
+    pub fn remove_edge(&mut self, from: VertexId, to: VertexId) -> Result<(), GraphError> {
+        let edges = &mut self.vertices.get_mut(&from).ok_or(GraphError::VertexNotFound(from))?.edges;
+        let before = edges.len();
+        edges.retain(|e| e.to != to);
+
+        if edges.len() == before {
+            return Err(GraphError::EdgeNotFound { from, to });
+        }
+
+        Ok(())
+    }
+
+    /// Collapses parallel edges to a single edge per target with the minimum
+    /// weight, keeping first-occurrence order. Returns how many were removed.
+    pub fn dedup_edges(&mut self) -> usize {
+        let mut removed = 0;
+
+        for vertex in self.vertices.values_mut() {
+            let mut lightest: HashMap<VertexId, u32> = HashMap::new();
+            for edge in &vertex.edges {
+                lightest.entry(edge.to).and_modify(|w| *w = (*w).min(edge.weight)).or_insert(edge.weight);
+            }
+
+            let before = vertex.edges.len();
+            let mut seen: HashSet<VertexId> = HashSet::new();
+            vertex.edges.retain(|e| seen.insert(e.to));
+            for edge in &mut vertex.edges {
+                edge.weight = lightest[&edge.to];
+            }
+            removed += before - vertex.edges.len();
+        }
+
+        removed
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.vertices.shrink_to_fit();
+
+        for vertex in self.vertices.values_mut() {
+            vertex.edges.shrink_to_fit();
+        }
+    }
+
+    /// Rough heap footprint: vertex map capacity times entry size plus the
+    /// capacity of every edge list.
+    pub fn estimated_memory_bytes(&self) -> usize {
+        let entry = std::mem::size_of::<(VertexId, Vertex)>();
+        let edges: usize = self.vertices.values().map(|v| v.edges.capacity()).sum();
+
+        self.vertices.capacity() * entry + edges * std::mem::size_of::<Edge>()
+    }
+
+    /// Position of `id` among the vertex ids in sorted order.
+    pub fn vertex_index(&self, id: VertexId) -> Option<usize> {
+        self.sorted_ids().binary_search(&id).ok()
+    }
+
+    /// The vertex id at `index` in sorted id order; inverse of `vertex_index`.
+    pub fn vertex_by_index(&self, index: usize) -> Option<VertexId> {
+        self.sorted_ids().get(index).copied()
+    }
+
+    fn sorted_ids(&self) -> Vec<VertexId> {
+        let mut ids: Vec<VertexId> = self.vertices.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Inserts many edges at once, creating vertices as needed, streaming them
+    /// straight from the iterator. A graph without parallel edges needs at least
+    /// `sqrt(E)` vertices to hold `E` edges, so the vertex map is reserved for
+    /// that many from the size hint: never more than dense input needs, and
+    /// the map grows as usual past it.
+    pub fn add_edges(&mut self, edges: impl IntoIterator<Item = (VertexId, VertexId, u32)>) {
+        let edges = edges.into_iter();
+        self.vertices.reserve(edges.size_hint().0.isqrt());
+
+        for (from, to, weight) in edges {
+            self.add_edge(from, to, weight);
+        }
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.vertices.values().map(|v| v.edges.len()).sum()
+    }
+
+    /// Every edge as `(from, to, weight)`, sorted by weight, then `from`, then `to`.
+    pub fn edges_by_weight(&self) -> Vec<(VertexId, VertexId, u32)> {
+        let mut edges: Vec<(VertexId, VertexId, u32)> = self.vertices
+            .values()
+            .flat_map(|v| v.edges.iter().map(move |e| (v.id, e.to, e.weight)))
+            .collect();
+
+        edges.sort_by_key(|&(from, to, weight)| (weight, from, to));
+        edges
+    }
+
+    /// Edges with exactly one endpoint in `set`, in either direction, sorted
+    /// by source, target and weight.
+    pub fn cut_edges(&self, set: &HashSet<VertexId>) -> Vec<(VertexId, VertexId, u32)> {
+        let mut edges: Vec<(VertexId, VertexId, u32)> = self.vertices
+            .values()
+            .flat_map(|v| v.edges.iter().map(move |e| (v.id, e.to, e.weight)))
+            .filter(|(from, to, _)| set.contains(from) != set.contains(to))
+            .collect();
+
+        edges.sort();
+        edges
+    }
+
+    /// Total weight of `cut_edges(set)`.
+    pub fn cut_weight(&self, set: &HashSet<VertexId>) -> u64 {
+        self.cut_edges(set).iter().map(|&(_, _, weight)| u64::from(weight)).sum()
+    }
+
+    /// Counts edges per weight bucket of width `bucket_size`, keyed by each
+    /// bucket's lower bound. A `bucket_size` of 0 puts every edge in bucket 0.
+    pub fn weight_histogram(&self, bucket_size: u32) -> BTreeMap<u32, usize> {
+        let mut histogram = BTreeMap::new();
+
+        for edge in self.vertices.values().flat_map(|v| &v.edges) {
+            let bucket = match bucket_size {
+                0 => 0,
+                size => edge.weight - edge.weight % size,
+            };
+            *histogram.entry(bucket).or_insert(0) += 1;
+        }
+
+        histogram
+    }
+
+    /// Cheapest outgoing edge weight per vertex, skipping vertices with none.
+    pub fn min_outgoing(&self) -> HashMap<VertexId, u32> {
+        self.vertices
+            .values()
+            .filter_map(|v| v.edges.iter().map(|e| e.weight).min().map(|w| (v.id, w)))
+            .collect()
+    }
+
+    /// Cheapest incoming edge weight per vertex, skipping vertices with none.
+    pub fn min_incoming(&self) -> HashMap<VertexId, u32> {
+        let mut minimums: HashMap<VertexId, u32> = HashMap::new();
+
+        for edge in self.vertices.values().flat_map(|v| &v.edges) {
+            if !self.vertices.contains_key(&edge.to) {
+                continue;
+            }
+
+            let weight = minimums.entry(edge.to).or_insert(edge.weight);
+            *weight = (*weight).min(edge.weight);
+        }
+
+        minimums
+    }
+
+    /// Maps each out-degree to the number of vertices having it.
+    pub fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+
+        for vertex in self.vertices.values() {
+            *distribution.entry(vertex.edges.len()).or_insert(0) += 1;
+        }
+
+        distribution
+    }
+
+    /// A `width` x `height` grid with 4-connected edges of uniform `weight` in
+    /// both directions, plus each vertex's `(x, y)` position. Vertices are
+    /// numbered row by row from `'a'` upwards.
+    pub fn grid(width: usize, height: usize, weight: u32) -> (Graph, HashMap<VertexId, (usize, usize)>) {
+        let mut graph = Graph::new();
+        let mut coordinates = HashMap::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let id = indexed_vertex(y * width + x);
+                coordinates.insert(id, (x, y));
+                graph.add_vertex(Vertex { id, edges: Vec::new() });
+
+                if x > 0 {
+                    let left = indexed_vertex(y * width + x - 1);
+                    graph.add_edge(id, left, weight);
+                    graph.add_edge(left, id, weight);
+                }
+                if y > 0 {
+                    let up = indexed_vertex((y - 1) * width + x);
+                    graph.add_edge(id, up, weight);
+                    graph.add_edge(up, id, weight);
+                }
+            }
+        }
+
+        (graph, coordinates)
+    }
+
+    /// Builds a graph from `(from, to, weight)` triples, idempotent on duplicates:
+    /// an exact repeat of a triple is kept once, while an edge listed again with
+    /// a different weight is rejected. Every endpoint becomes a vertex, so the
+    /// result always passes `validate`.
+    pub fn try_from_edges(edges: &[(char, char, u32)]) -> Result<Graph, GraphError> {
+        let mut seen: HashMap<(VertexId, VertexId), u32> = HashMap::new();
+        let mut graph = Graph::new();
+
+        for &(from, to, weight) in edges {
+            let (from, to) = (VertexId(from), VertexId(to));
+
+            match seen.get(&(from, to)) {
+                Some(&existing) if existing != weight => {
+                    return Err(GraphError::ConflictingEdge { from, to });
+                }
+                Some(_) => continue,
+                None => {
+                    seen.insert((from, to), weight);
+                    graph.add_edge(from, to, weight);
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Checks that every vertex is stored under its own id and that every edge
+    /// points at a vertex present in the graph.
+    pub fn validate(&self) -> Result<(), GraphError> {
+        for (key, vertex) in &self.vertices {
+            if *key != vertex.id {
+                return Err(GraphError::KeyMismatch { key: *key, id: vertex.id });
+            }
+
+            for edge in &vertex.edges {
+                if !self.vertices.contains_key(&edge.to) {
+                    return Err(GraphError::VertexNotFound(edge.to));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn edge_weight(&self, from: VertexId, to: VertexId) -> Option<u32> {
+        self.vertices
+            .get(&from)?
+            .edges
+            .iter()
+            .filter(|e| e.to == to)
+            .map(|e| e.weight)
+            .min()
+    }
+
+    /// Looks up the (minimum) weight of each queried edge, in order. Large
+    /// batches build a temporary `(from, to)` index instead of scanning edges.
+    pub fn edge_weights(&self, pairs: &[(VertexId, VertexId)]) -> Vec<Option<u32>> {
+        if pairs.len() < EDGE_INDEX_THRESHOLD {
+            return pairs.iter().map(|&(from, to)| self.edge_weight(from, to)).collect();
+        }
+
+        let mut index: HashMap<(VertexId, VertexId), u32> = HashMap::new();
+
+        for vertex in self.vertices.values() {
+            for edge in &vertex.edges {
+                let weight = index.entry((vertex.id, edge.to)).or_insert(edge.weight);
+                *weight = (*weight).min(edge.weight);
+            }
+        }
+
+        pairs.iter().map(|pair| index.get(pair).copied()).collect()
+    }
+
+    pub fn dijkstra(&self, start: VertexId) -> HashMap<VertexId, u32> {
+        self.shortest_path_tree(start, None).0
+    }
+
+    /// Reachable vertices and their distances from `start`, sorted by id.
+    pub fn dijkstra_sorted(&self, start: VertexId) -> Vec<(VertexId, u32)> {
+        let mut distances: Vec<(VertexId, u32)> = self.dijkstra(start).into_iter().collect();
+        distances.sort();
+        distances
+    }
+
+    /// Vertices grouped by their distance from `start`, in ascending distance
+    /// order with ids sorted within each group.
+    pub fn dijkstra_levels(&self, start: VertexId) -> Vec<(u32, Vec<VertexId>)> {
+        let mut levels: BTreeMap<u32, Vec<VertexId>> = BTreeMap::new();
+
+        for (vertex, distance) in self.dijkstra(start) {
+            levels.entry(distance).or_default().push(vertex);
+        }
+
+        levels
+            .into_iter()
+            .map(|(distance, mut vertices)| {
+                vertices.sort();
+                (distance, vertices)
+            })
+            .collect()
+    }
+
+    /// Every vertex mapped to `Some(distance)` from `start`, or `None` if it is
+    /// unreachable.
+    pub fn dijkstra_dense(&self, start: VertexId) -> HashMap<VertexId, Option<u32>> {
+        let distances = self.dijkstra(start);
+
+        self.vertices.keys().map(|id| (*id, distances.get(id).copied())).collect()
+    }
+
+    /// The `k` closest vertices to `start` (excluding `start`) in ascending
+    /// distance order, ties broken by id. Zero-weight edges can settle a tie
+    /// out of id order, so the search runs on through the whole tie group at
+    /// the `k`-th distance and halts at the first vertex beyond it.
+    pub fn k_nearest(&self, start: VertexId, k: usize) -> Vec<(VertexId, u32)> {
+        self.k_nearest_with_settled(start, k).0
+    }
+
+    fn k_nearest_with_settled(&self, start: VertexId, k: usize) -> (Vec<(VertexId, u32)>, usize) {
+        let mut nearest: Vec<(VertexId, u32)> = Vec::new();
+        let mut settled = 0;
+
+        self.shortest_path_tree_until(start, |_, _| true, |vertex, distance| {
+            settled += 1;
+
+            if k == 0 || (nearest.len() >= k && distance > nearest[k - 1].1) {
+                return true;
+            }
+
+            if vertex != start {
+                nearest.push((vertex, distance));
+            }
+
+            false
+        });
+
+        nearest.sort_by_key(|&(vertex, distance)| (distance, vertex));
+        nearest.truncate(k);
+        (nearest, settled)
+    }
+
+    /// Vertices whose shortest distance from `start` is exactly `distance`, sorted.
+    pub fn at_distance(&self, start: VertexId, distance: u32) -> Vec<VertexId> {
+        self.between_distances(start, distance, distance)
+    }
+
+    /// Vertices whose shortest distance from `start` lies in `min..=max`, sorted.
+    pub fn between_distances(&self, start: VertexId, min: u32, max: u32) -> Vec<VertexId> {
+        let mut vertices: Vec<VertexId> = self.dijkstra(start)
+            .into_iter()
+            .filter(|&(_, d)| min <= d && d <= max)
+            .map(|(v, _)| v)
+            .collect();
+
+        vertices.sort();
+        vertices
+    }
+
+    /// Dijkstra over weights produced by `weight(from, edge)` instead of the
+    /// stored `u32`s. Any totally ordered, addable `W` whose `Default` is zero
+    /// works, e.g. `std::time::Duration` (its default is `Duration::ZERO`),
+    /// which gives shortest travel times. Weights must not be negative; debug
+    /// builds panic if a settled vertex is later reached more cheaply.
+    pub fn dijkstra_by<W, F>(&self, start: VertexId, weight: F) -> HashMap<VertexId, W>
+    where
+        W: Copy + Ord + Add<Output = W> + Default,
+        F: Fn(VertexId, &Edge) -> W
+    {
+        let mut distances: HashMap<VertexId, W> = HashMap::new();
         let mut visited: HashSet<VertexId> = HashSet::new();
 
         let mut priority_queue = BinaryHeap::new();
 
-        distances.insert(start.id.clone(), 0);
-        priority_queue.push(State { vertex: start.id, cost: 0 });
+        if !self.vertices.contains_key(&start) {
+            return distances;
+        }
 
-        while let Some(State { vertex: current_vertex, cost: current_distance }) = priority_queue.pop() {
+        distances.insert(start, W::default());
+        priority_queue.push(Reverse((W::default(), start)));
+
+        while let Some(Reverse((current_distance, current_vertex))) = priority_queue.pop() {
             if !visited.insert(current_vertex) {
                 continue;
             }
 
-            if let Some(v) = self.vertices.get(&current_vertex) {
-                for neighbor in &v.edges {
-                    if let Some(next) = self.vertices.get(&neighbor.to) {
-                        let distance = current_distance + neighbor.weight;
+            for neighbor in &self.vertices[&current_vertex].edges {
+                if !self.vertices.contains_key(&neighbor.to) {
+                    continue;
+                }
 
-                        if distance < *distances.get(&neighbor.to).unwrap_or(&u32::MAX) {
-                            distances.insert(neighbor.to.clone(), distance);
-                            priority_queue.push(State { vertex: next.id, cost: distance });
-                        }
+                let distance = current_distance + weight(current_vertex, neighbor);
+
+                if visited.contains(&neighbor.to) {
+                    debug_assert!(
+                        distance >= distances[&neighbor.to],
+                        "settled vertex {} reached more cheaply; negative edge weight from {}",
+                        neighbor.to,
+                        current_vertex
+                    );
+                    continue;
+                }
+
+                if distances.get(&neighbor.to).is_none_or(|&known| distance < known) {
+                    distances.insert(neighbor.to, distance);
+                    priority_queue.push(Reverse((distance, neighbor.to)));
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Label-setting search generalised over how path values combine and
+    /// compare: `init` is the value of the empty path at `start`,
+    /// `combine(value, weight)` extends a path by one edge and `better(a, b)`
+    /// says whether `a` beats `b`. `(u32::MAX, min, >)` gives the widest path
+    /// and `(0, +, <)` plain Dijkstra. The answer is only optimal when values
+    /// form an ordered monotone semiring: `better` must be a strict total
+    /// order and extending a path must never make it better. The frontier is
+    /// scanned linearly since `better` need not agree with any `Ord`; ties
+    /// settle the smaller id first.
+    pub fn best_path<C, Combine, Better>(&self, start: VertexId, goal: VertexId, init: C, combine: Combine, better: Better) -> Option<(C, Vec<VertexId>)>
+    where
+        C: Clone,
+        Combine: Fn(&C, u32) -> C,
+        Better: Fn(&C, &C) -> bool
+    {
+        if !self.vertices.contains_key(&start) {
+            return None;
+        }
+
+        let mut labels: HashMap<VertexId, C> = HashMap::from([(start, init)]);
+        let mut predecessors: HashMap<VertexId, VertexId> = HashMap::new();
+        let mut settled: HashSet<VertexId> = HashSet::new();
+
+        loop {
+            let current = labels
+                .iter()
+                .filter(|(id, _)| !settled.contains(id))
+                .reduce(|best, candidate| {
+                    let (best_id, best_label) = best;
+                    let (candidate_id, candidate_label) = candidate;
+
+                    if better(candidate_label, best_label) || (!better(best_label, candidate_label) && candidate_id < best_id) {
+                        candidate
+                    } else {
+                        best
                     }
+                })
+                .map(|(&id, _)| id)?;
+
+            settled.insert(current);
+
+            if current == goal {
+                let label = labels.remove(&goal).unwrap();
+                return Some((label, reconstruct_path(&predecessors, start, goal)));
+            }
+
+            for edge in &self.vertices[&current].edges {
+                if settled.contains(&edge.to) || !self.vertices.contains_key(&edge.to) {
+                    continue;
+                }
+
+                let candidate = combine(&labels[&current], edge.weight);
+
+                if labels.get(&edge.to).is_none_or(|known| better(&candidate, known)) {
+                    labels.insert(edge.to, candidate);
+                    predecessors.insert(edge.to, current);
                 }
             }
         }
     }
 
-    pub fn dijkstra_no_heap(&mut self, start: Vertex) {
+    /// Dijkstra using `weight_fn(from, to, stored_weight)` as each edge's
+    /// effective weight, so weights can be overridden without mutating the graph.
+    pub fn dijkstra_with_weight_fn<F: Fn(VertexId, VertexId, u32) -> u32>(&self, start: VertexId, weight_fn: F) -> HashMap<VertexId, u32> {
+        self.dijkstra_by(start, |from, edge| weight_fn(from, edge.to, edge.weight))
+    }
+
+    /// Cheapest distances from `start` using at most `max_hops` edges. A vertex
+    /// may be cheaper via more hops, so this runs `max_hops` Bellman-Ford style
+    /// rounds, each extending the previous round's paths by one edge.
+    pub fn dijkstra_max_hops(&self, start: VertexId, max_hops: usize) -> HashMap<VertexId, u32> {
         let mut distances: HashMap<VertexId, u32> = HashMap::new();
-        let mut visited: HashSet<VertexId> = HashSet::new();
 
-        distances.insert(start.id.clone(), 0);
+        if !self.vertices.contains_key(&start) {
+            return distances;
+        }
+
+        distances.insert(start, 0);
 
-        let mut current_vertex = start.id.clone();
-        let graph_len = self.vertices.keys().len();
+        for _ in 0..max_hops {
+            let mut next = distances.clone();
 
-        while visited.len() < graph_len {
-            visited.insert(current_vertex);
-            let current_distance = *distances.get(&current_vertex).unwrap_or(&u32::MAX);
+            for (&from, &current_distance) in &distances {
+                for neighbor in &self.vertices[&from].edges {
+                    if !self.vertices.contains_key(&neighbor.to) {
+                        continue;
+                    }
 
-            if let Some(v) = self.vertices.get(&current_vertex) {
-                for neighbor in &v.edges {
                     let distance = current_distance + neighbor.weight;
 
-                    if distance < *distances.get(&neighbor.to).unwrap_or(&u32::MAX) {
-                        distances.insert(neighbor.to, distance);
+                    if distance < *next.get(&neighbor.to).unwrap_or(&u32::MAX) {
+                        next.insert(neighbor.to, distance);
                     }
                 }
             }
 
-            let next_vertex = self.vertices
-                .iter()
-                .filter(|(_, v)| !visited.contains(&v.id))
-                .min_by_key(|(_, v)| distances.get(&v.id).unwrap_or(&u32::MAX))
-                .map(|(_, v)| v.clone());
-
-            match next_vertex {
-                Some(v) => current_vertex = v.id,
-                None => break,
+            if next == distances {
+                break;
             }
+
+            distances = next;
         }
+
+        distances
     }
-}
 
-#[derive(Eq, PartialEq)]
-struct State {
-    vertex: VertexId,
-    cost: u32
-}
+    /// The vertex reachable from both `a` and `b` with the smallest summed
+    /// distance, ties broken by id.
+    pub fn best_meeting_point(&self, a: VertexId, b: VertexId) -> Option<(VertexId, u32)> {
+        let from_a = self.dijkstra(a);
+        let from_b = self.dijkstra(b);
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.cost.cmp(&self.cost)
+        from_a
+            .iter()
+            .filter_map(|(v, da)| from_b.get(v).map(|db| (da + db, *v)))
+            .min()
+            .map(|(total, v)| (v, total))
     }
-}
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// Starts a constrained shortest-path query from `start`, e.g.
+    /// `graph.query(start).to(goal).avoiding(&blocked).within(100).max_hops(5).run()`.
+    pub fn query(&self, start: VertexId) -> Query<'_> {
+        Query {
+            graph: self,
+            start,
+            goal: None,
+            blocked: HashSet::new(),
+            max_distance: None,
+            max_hops: None
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn shortest_path(&self, start: VertexId, goal: VertexId) -> Option<Vec<VertexId>> {
+        self.shortest_path_with_cost(start, goal).map(|(_, path)| path)
+    }
 
-    #[test]
-    fn it_works() {
-        assert_eq!(true, true);
+    /// Walks the same shortest path as `shortest_path`, start first, without
+    /// collecting it into a `Vec`: the goal's chain in the forward
+    /// predecessor map is reversed in place into successor links.
+    pub fn shortest_path_iter(&self, start: VertexId, goal: VertexId) -> Option<impl Iterator<Item = VertexId>> {
+        let (distances, mut links) = self.shortest_path_tree(start, Some(goal));
+        distances.get(&goal)?;
+
+        let mut successor = None;
+        let mut current = goal;
+
+        loop {
+            let previous = links.get(&current).copied();
+
+            match successor {
+                Some(next) => links.insert(current, next),
+                None => links.remove(&current),
+            };
+
+            if current == start {
+                break;
+            }
+
+            successor = Some(current);
+            current = previous.unwrap();
+        }
+
+        Some(std::iter::successors(Some(start), move |current| {
+            if *current == goal { None } else { links.get(current).copied() }
+        }))
+    }
+
+    /// Returns the total cost together with the route, taking the cost straight
+    /// from the distance map rather than re-summing the path.
+    pub fn shortest_path_with_cost(&self, start: VertexId, goal: VertexId) -> Option<(u32, Vec<VertexId>)> {
+        let (distances, predecessors) = self.shortest_path_tree(start, Some(goal));
+        let cost = *distances.get(&goal)?;
+
+        Some((cost, reconstruct_path(&predecessors, start, goal)))
+    }
+
+    /// Shortest path that never passes through a vertex in `blocked`, or
+    /// `Ok(None)` when every route is blocked. Blocking `start` or `goal`
+    /// itself is an error, kept apart from the no-route case.
+    pub fn shortest_path_avoiding(&self, start: VertexId, goal: VertexId, blocked: &HashSet<VertexId>) -> Result<Option<(u32, Vec<VertexId>)>, GraphError> {
+        if let Some(&endpoint) = [start, goal].iter().find(|id| blocked.contains(id)) {
+            return Err(GraphError::BlockedEndpoint(endpoint));
+        }
+
+        let (distances, predecessors) = self.shortest_path_tree_filtered(start, Some(goal), |_, e| !blocked.contains(&e.to));
+
+        Ok(distances.get(&goal).map(|&cost| (cost, reconstruct_path(&predecessors, start, goal))))
+    }
+
+    /// Shortest path where moving `prev -> current -> next` costs an extra
+    /// `turn_cost(prev, current, next)` on top of the edge weight. The search
+    /// runs over `(previous, current)` states rather than plain vertices.
+    pub fn shortest_path_with_turn_cost<F: Fn(VertexId, VertexId, VertexId) -> u32>(&self, start: VertexId, goal: VertexId, turn_cost: F) -> Option<(u32, Vec<VertexId>)> {
+        type TurnState = (Option<VertexId>, VertexId);
+
+        let mut distances: HashMap<TurnState, u32> = HashMap::new();
+        let mut came_from: HashMap<TurnState, TurnState> = HashMap::new();
+        let mut settled: HashSet<TurnState> = HashSet::new();
+
+        let mut priority_queue = BinaryHeap::new();
+
+        if !self.vertices.contains_key(&start) {
+            return None;
+        }
+
+        distances.insert((None, start), 0);
+        priority_queue.push(Reverse((0, start, None)));
+
+        while let Some(Reverse((current_distance, current_vertex, previous))) = priority_queue.pop() {
+            let state = (previous, current_vertex);
+
+            if !settled.insert(state) {
+                continue;
+            }
+
+            if current_vertex == goal {
+                let mut path = vec![current_vertex];
+                let mut current = state;
+
+                while let Some(&before) = came_from.get(&current) {
+                    path.push(before.1);
+                    current = before;
+                }
+
+                path.reverse();
+                return Some((current_distance, path));
+            }
+
+            for neighbor in &self.vertices[&current_vertex].edges {
+                if !self.vertices.contains_key(&neighbor.to) {
+                    continue;
+                }
+
+                let penalty = previous.map_or(0, |p| turn_cost(p, current_vertex, neighbor.to));
+                let distance = current_distance + neighbor.weight + penalty;
+                let next = (Some(current_vertex), neighbor.to);
+
+                if distance < *distances.get(&next).unwrap_or(&u32::MAX) {
+                    distances.insert(next, distance);
+                    came_from.insert(next, state);
+                    priority_queue.push(Reverse((distance, neighbor.to, Some(current_vertex))));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The shortest-path tree rooted at `start` as nested nodes, with children
+    /// sorted by id. Returns `None` if `start` is not in the graph.
+    pub fn spt_as_tree(&self, start: VertexId) -> Option<SptNode> {
+        fn build(id: VertexId, distances: &HashMap<VertexId, u32>, children: &HashMap<VertexId, Vec<VertexId>>) -> SptNode {
+            SptNode {
+                id,
+                distance: distances[&id],
+                children: children
+                    .get(&id)
+                    .into_iter()
+                    .flatten()
+                    .map(|&child| build(child, distances, children))
+                    .collect()
+            }
+        }
+
+        if !self.vertices.contains_key(&start) {
+            return None;
+        }
+
+        let (distances, predecessors) = self.shortest_path_tree(start, None);
+        let mut children: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+
+        for (&child, &parent) in &predecessors {
+            children.entry(parent).or_default().push(child);
+        }
+        for list in children.values_mut() {
+            list.sort();
+        }
+
+        Some(build(start, &distances, &children))
+    }
+
+    /// The shortest-path tree rooted at `start` as a standalone `Graph` holding
+    /// the reachable vertices and their parent -> child tree edges.
+    pub fn spt_graph(&self, start: VertexId) -> Graph {
+        let (distances, predecessors) = self.shortest_path_tree(start, None);
+        let mut tree: Graph = distances.keys().map(|&id| Vertex { id, edges: Vec::new() }).collect();
+
+        for (&child, &parent) in &predecessors {
+            tree.add_edge(parent, child, self.edge_weight(parent, child).unwrap());
+        }
+
+        tree
+    }
+
+    /// The edge on the shortest path whose removal raises the `start` -> `goal`
+    /// distance the most, with the distance after removing it (`u32::MAX` if
+    /// that disconnects `goal`). Ties go to the edge nearest `start`.
+    pub fn most_critical_edge(&self, start: VertexId, goal: VertexId) -> Option<((VertexId, VertexId), u32)> {
+        let path = self.shortest_path(start, goal)?;
+        let mut critical: Option<((VertexId, VertexId), u32)> = None;
+
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let (distances, _) = self.shortest_path_tree_filtered(start, Some(goal), |v, e| !(v == from && e.to == to));
+            let distance = distances.get(&goal).copied().unwrap_or(u32::MAX);
+
+            if critical.is_none_or(|(_, worst)| distance > worst) {
+                critical = Some(((from, to), distance));
+            }
+        }
+
+        critical
+    }
+
+    /// The shortest path as `(from, to, weight)` hops.
+    pub fn shortest_path_edges(&self, start: VertexId, goal: VertexId) -> Option<Vec<(VertexId, VertexId, u32)>> {
+        let path = self.shortest_path(start, goal)?;
+
+        Some(path.windows(2).map(|w| (w[0], w[1], self.edge_weight(w[0], w[1]).unwrap())).collect())
+    }
+
+    /// The heaviest hop on the shortest path and its endpoints (the first one
+    /// if several tie). `None` if `goal` is unreachable or equal to `start`.
+    pub fn shortest_path_bottleneck(&self, start: VertexId, goal: VertexId) -> Option<(u32, (VertexId, VertexId))> {
+        self.shortest_path_edges(start, goal)?
+            .into_iter()
+            .fold(None, |heaviest, (from, to, weight)| match heaviest {
+                Some((max, _)) if max >= weight => heaviest,
+                _ => Some((weight, (from, to))),
+            })
+    }
+
+    /// A* search guided by `heuristic`, which must never overestimate the
+    /// remaining distance to `goal` for the result to be optimal.
+    pub fn astar<H: Fn(VertexId) -> u32>(&self, start: VertexId, goal: VertexId, heuristic: H) -> Option<(u32, Vec<VertexId>)> {
+        AStarContext::new().search(self, start, goal, heuristic)
+    }
+
+    /// Iterative-deepening A*: repeated depth-first searches bounded by a rising
+    /// f-cost threshold. Memory use is linear in the path length, at the price
+    /// of revisiting vertices many times, so it suits graphs whose A* frontier
+    /// would be too large to hold. `heuristic` must be admissible.
+    pub fn ida_star<H: Fn(VertexId) -> u32>(&self, start: VertexId, goal: VertexId, heuristic: H) -> Option<(u32, Vec<VertexId>)> {
+        fn search<H: Fn(VertexId) -> u32>(graph: &Graph, path: &mut Vec<VertexId>, cost: u32, threshold: u32, goal: VertexId, heuristic: &H) -> Result<u32, u32> {
+            let current = *path.last().unwrap();
+            let estimate = cost + heuristic(current);
+
+            if estimate > threshold {
+                return Err(estimate);
+            }
+
+            if current == goal {
+                return Ok(cost);
+            }
+
+            let mut next_threshold = u32::MAX;
+
+            for neighbor in &graph.vertices[&current].edges {
+                if !graph.vertices.contains_key(&neighbor.to) || path.contains(&neighbor.to) {
+                    continue;
+                }
+
+                path.push(neighbor.to);
+
+                match search(graph, path, cost + neighbor.weight, threshold, goal, heuristic) {
+                    Ok(found) => return Ok(found),
+                    Err(exceeded) => next_threshold = next_threshold.min(exceeded),
+                }
+
+                path.pop();
+            }
+
+            Err(next_threshold)
+        }
+
+        if !self.vertices.contains_key(&start) {
+            return None;
+        }
+
+        let mut threshold = heuristic(start);
+        let mut path = vec![start];
+
+        loop {
+            match search(self, &mut path, 0, threshold, goal, &heuristic) {
+                Ok(cost) => return Some((cost, path)),
+                Err(u32::MAX) => return None,
+                Err(next) => threshold = next,
+            }
+        }
+    }
+
+    /// Checks that `path` is made of real edges and that its cost equals the
+    /// shortest distance between its endpoints. Returns the verified cost.
+    pub fn verify_shortest_path(&self, path: &[VertexId]) -> Result<u32, PathError> {
+        let (&start, &goal) = match (path.first(), path.last()) {
+            (Some(start), Some(goal)) => (start, goal),
+            _ => return Err(PathError::Empty),
+        };
+
+        if let Some(&missing) = path.iter().find(|id| !self.vertices.contains_key(id)) {
+            return Err(PathError::VertexNotFound(missing));
+        }
+
+        let mut claimed = 0;
+
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            claimed += self.edge_weight(from, to).ok_or(PathError::MissingEdge { from, to })?;
+        }
+
+        let optimal = *self.shortest_path_tree(start, Some(goal)).0.get(&goal).ok_or(PathError::VertexNotFound(goal))?;
+
+        if claimed == optimal {
+            Ok(claimed)
+        } else {
+            Err(PathError::Suboptimal { claimed, optimal })
+        }
+    }
+
+    /// Runs Dijkstra from `start`, stopping early once `goal` (if any) is settled.
+    /// Returns the distance map and the predecessor of every reached vertex.
+    fn shortest_path_tree(&self, start: VertexId, goal: Option<VertexId>) -> (HashMap<VertexId, u32>, HashMap<VertexId, VertexId>) {
+        self.shortest_path_tree_filtered(start, goal, |_, _| true)
+    }
+
+    /// `shortest_path_tree` restricted to the edges accepted by `allow_edge`.
+    fn shortest_path_tree_filtered<F: Fn(VertexId, &Edge) -> bool>(&self, start: VertexId, goal: Option<VertexId>, allow_edge: F) -> (HashMap<VertexId, u32>, HashMap<VertexId, VertexId>) {
+        self.shortest_path_tree_until(start, allow_edge, |vertex, _| goal == Some(vertex))
+    }
+
+    /// The Dijkstra loop behind the shortest-path tree helpers. `on_settle` sees
+    /// every vertex as it is settled, with its final distance, and stops the
+    /// search by returning `true`; that vertex's edges are then not relaxed.
+    fn shortest_path_tree_until<F, S>(&self, start: VertexId, allow_edge: F, mut on_settle: S) -> (HashMap<VertexId, u32>, HashMap<VertexId, VertexId>)
+    where
+        F: Fn(VertexId, &Edge) -> bool,
+        S: FnMut(VertexId, u32) -> bool
+    {
+        let mut distances: HashMap<VertexId, u32> = HashMap::new();
+        let mut predecessors: HashMap<VertexId, VertexId> = HashMap::new();
+        let mut visited: HashSet<VertexId> = HashSet::new();
+
+        let mut priority_queue = BinaryHeap::new();
+
+        if !self.vertices.contains_key(&start) {
+            return (distances, predecessors);
+        }
+
+        distances.insert(start, 0);
+        priority_queue.push(State { vertex: start, cost: 0 });
+
+        while let Some(State { vertex: current_vertex, cost: current_distance }) = priority_queue.pop() {
+            if !visited.insert(current_vertex) {
+                continue;
+            }
+
+            if on_settle(current_vertex, current_distance) {
+                break;
+            }
+
+            if let Some(v) = self.vertices.get(&current_vertex) {
+                for neighbor in &v.edges {
+                    if !self.vertices.contains_key(&neighbor.to) || !allow_edge(current_vertex, neighbor) {
+                        continue;
+                    }
+
+                    let distance = current_distance + neighbor.weight;
+
+                    if distance < *distances.get(&neighbor.to).unwrap_or(&u32::MAX) {
+                        distances.insert(neighbor.to, distance);
+                        predecessors.insert(neighbor.to, current_vertex);
+                        priority_queue.push(State { vertex: neighbor.to, cost: distance });
+                    }
+                }
+            }
+        }
+
+        (distances, predecessors)
+    }
+
+    /// Enumerates every distinct simple shortest path from `start` to `goal`,
+    /// sorted. The number of optimal paths can grow exponentially, so
+    /// enumeration stops after `MAX_SHORTEST_PATHS` paths.
+    pub fn all_shortest_paths(&self, start: VertexId, goal: VertexId) -> Vec<Vec<VertexId>> {
+        let ShortestPathDag { distances, predecessors, .. } = self.shortest_path_dag(start);
+        let mut paths = Vec::new();
+
+        if !distances.contains_key(&goal) {
+            return paths;
+        }
+
+        for_each_tight_path(&predecessors, start, goal, |reversed| {
+            paths.push(reversed.iter().rev().copied().collect());
+            paths.len() < MAX_SHORTEST_PATHS
+        });
+
+        paths.sort();
+        paths
+    }
+
+    /// Counts the distinct simple shortest paths from `start` to `goal` (0 if
+    /// unreachable) by accumulating path counts over the predecessor DAG in
+    /// topological order. Counts saturate at `u64::MAX` on pathological
+    /// graphs. A reachable zero-weight cycle leaves no such order, and then
+    /// the paths are walked one by one, which takes exponential time.
+    pub fn count_shortest_paths(&self, start: VertexId, goal: VertexId) -> u64 {
+        let ShortestPathDag { distances, predecessors, order } = self.shortest_path_dag(start);
+
+        if !distances.contains_key(&goal) {
+            return 0;
+        }
+
+        let Some(order) = order else {
+            let mut count = 0u64;
+            for_each_tight_path(&predecessors, start, goal, |_| {
+                count = count.saturating_add(1);
+                true
+            });
+            return count;
+        };
+
+        let mut sigma: HashMap<VertexId, u64> = HashMap::new();
+
+        for vertex in order {
+            let count = if vertex == start {
+                1
+            } else {
+                predecessors[&vertex]
+                    .iter()
+                    .fold(0u64, |total, previous| total.saturating_add(sigma[previous]))
+            };
+
+            if vertex == goal {
+                return count;
+            }
+
+            sigma.insert(vertex, count);
+        }
+
+        0
+    }
+
+    /// For every edge, the sum over all source-target pairs of the fraction of
+    /// simple shortest paths that use it (Brandes' accumulation applied to
+    /// edges). Parallel edges share a single `(from, to)` entry. Sources that
+    /// reach a zero-weight cycle fall back to walking every path, which takes
+    /// exponential time.
+    pub fn edge_betweenness(&self) -> HashMap<(VertexId, VertexId), f64> {
+        let mut betweenness: HashMap<(VertexId, VertexId), f64> = self.vertices
+            .values()
+            .flat_map(|v| v.edges.iter().filter(|e| self.vertices.contains_key(&e.to)).map(move |e| ((v.id, e.to), 0.0)))
+            .collect();
+
+        for &source in self.vertices.keys() {
+            let ShortestPathDag { distances, predecessors, order } = self.shortest_path_dag(source);
+
+            let Some(order) = order else {
+                for &target in distances.keys().filter(|&&target| target != source) {
+                    let mut paths = 0.0;
+                    let mut uses: HashMap<(VertexId, VertexId), f64> = HashMap::new();
+
+                    for_each_tight_path(&predecessors, source, target, |reversed| {
+                        paths += 1.0;
+                        for pair in reversed.windows(2) {
+                            *uses.entry((pair[1], pair[0])).or_insert(0.0) += 1.0;
+                        }
+                        true
+                    });
+
+                    for (edge, count) in uses {
+                        *betweenness.get_mut(&edge).unwrap() += count / paths;
+                    }
+                }
+                continue;
+            };
+
+            let mut sigma: HashMap<VertexId, f64> = HashMap::from([(source, 1.0)]);
+
+            for vertex in &order[1..] {
+                let count = predecessors[vertex].iter().map(|p| sigma[p]).sum();
+                sigma.insert(*vertex, count);
+            }
+
+            let mut delta: HashMap<VertexId, f64> = HashMap::new();
+
+            for target in order.iter().rev() {
+                let dependency = 1.0 + delta.get(target).copied().unwrap_or(0.0);
+
+                for previous in predecessors.get(target).into_iter().flatten() {
+                    let share = sigma[previous] / sigma[target] * dependency;
+                    *betweenness.get_mut(&(*previous, *target)).unwrap() += share;
+                    *delta.entry(*previous).or_insert(0.0) += share;
+                }
+            }
+        }
+
+        betweenness
+    }
+
+    /// Up to `count` edge-disjoint paths from `start` to `goal` with the least
+    /// total weight, found by successive shortest augmenting paths over unit
+    /// capacities. Parallel edges count as separate edges. Fewer paths are
+    /// returned when the graph does not have `count` disjoint routes.
+    pub fn edge_disjoint_paths(&self, start: VertexId, goal: VertexId, count: usize) -> Vec<Vec<VertexId>> {
+        struct Arc {
+            to: usize,
+            cost: i64,
+            capacity: u8,
+            reverse: usize,
+            original: bool
+        }
+
+        let ids = self.sorted_ids();
+        let (source, sink) = match (ids.binary_search(&start), ids.binary_search(&goal)) {
+            (Ok(source), Ok(sink)) if source != sink => (source, sink),
+            _ => return Vec::new(),
+        };
+
+        let mut arcs: Vec<Arc> = Vec::new();
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); ids.len()];
+
+        for (from, id) in ids.iter().enumerate() {
+            for edge in &self.vertices[id].edges {
+                let to = match ids.binary_search(&edge.to) {
+                    Ok(to) if to != from => to,
+                    _ => continue,
+                };
+
+                let forward = arcs.len();
+                arcs.push(Arc { to, cost: edge.weight as i64, capacity: 1, reverse: forward + 1, original: true });
+                arcs.push(Arc { to: from, cost: -(edge.weight as i64), capacity: 0, reverse: forward, original: false });
+                outgoing[from].push(forward);
+                outgoing[to].push(forward + 1);
+            }
+        }
+
+        let mut flow = 0;
+
+        while flow < count {
+            // Bellman-Ford, since residual arcs carry negative costs.
+            let mut distance: Vec<Option<i64>> = vec![None; ids.len()];
+            let mut via: Vec<Option<usize>> = vec![None; ids.len()];
+            distance[source] = Some(0);
+
+            for _ in 0..ids.len() {
+                let mut changed = false;
+
+                for from in 0..ids.len() {
+                    let Some(base) = distance[from] else { continue };
+
+                    for &arc in &outgoing[from] {
+                        let Arc { to, cost, capacity, .. } = arcs[arc];
+
+                        if capacity > 0 && distance[to].is_none_or(|known| base + cost < known) {
+                            distance[to] = Some(base + cost);
+                            via[to] = Some(arc);
+                            changed = true;
+                        }
+                    }
+                }
+
+                if !changed {
+                    break;
+                }
+            }
+
+            if distance[sink].is_none() {
+                break;
+            }
+
+            let mut current = sink;
+            while current != source {
+                let arc = via[current].unwrap();
+                let reverse = arcs[arc].reverse;
+
+                arcs[arc].capacity -= 1;
+                arcs[reverse].capacity += 1;
+                current = arcs[reverse].to;
+            }
+
+            flow += 1;
+        }
+
+        // Each unit of flow is one path along saturated original arcs; any loop
+        // picked up on the way carries no useful flow and is cut out.
+        let mut paths = Vec::new();
+
+        for _ in 0..flow {
+            let mut path = vec![source];
+            let mut current = source;
+
+            while current != sink {
+                let arc = *outgoing[current]
+                    .iter()
+                    .find(|&&arc| arcs[arc].original && arcs[arc].capacity == 0)
+                    .unwrap();
+
+                arcs[arc].capacity = 1;
+                current = arcs[arc].to;
+
+                match path.iter().position(|&v| v == current) {
+                    Some(seen) => path.truncate(seen + 1),
+                    None => path.push(current),
+                }
+            }
+
+            paths.push(path.into_iter().map(|i| ids[i]).collect());
+        }
+
+        paths
+    }
+
+    /// Maximum flow from `source` to `sink` by Edmonds-Karp, treating edge
+    /// weights as capacities; parallel edges add up. Saturates at `u32::MAX`.
+    pub fn max_flow(&self, source: VertexId, sink: VertexId) -> u32 {
+        self.max_flow_residual(source, sink).flow
+    }
+
+    /// Minimum `source`-`sink` cut: its value, which equals `max_flow`, and
+    /// the sorted `(from, to)` pairs leaving the vertices still reachable from
+    /// `source` in the residual graph. Edges to ids that are not vertices carry
+    /// no flow and are left out.
+    pub fn min_cut(&self, source: VertexId, sink: VertexId) -> (u32, Vec<(VertexId, VertexId)>) {
+        let ResidualNetwork { flow, capacities: residual, neighbors } = self.max_flow_residual(source, sink);
+
+        let mut reachable: HashSet<VertexId> = HashSet::new();
+        let mut queue = VecDeque::new();
+        if self.vertices.contains_key(&source) {
+            reachable.insert(source);
+            queue.push_back(source);
+        }
+
+        while let Some(current) = queue.pop_front() {
+            for &next in &neighbors[&current] {
+                if residual[&(current, next)] > 0 && reachable.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut cut: Vec<(VertexId, VertexId)> = self.vertices
+            .values()
+            .filter(|v| reachable.contains(&v.id))
+            .flat_map(|v| v.edges.iter().map(move |e| (v.id, e.to)))
+            .filter(|(from, to)| from != to && self.vertices.contains_key(to) && !reachable.contains(to))
+            .collect();
+
+        cut.sort();
+        cut.dedup();
+        (flow, cut)
+    }
+
+    /// Runs Edmonds-Karp and returns the flow value alongside the final
+    /// residual capacities and the residual neighbours of every vertex.
+    fn max_flow_residual(&self, source: VertexId, sink: VertexId) -> ResidualNetwork {
+        let mut residual: HashMap<(VertexId, VertexId), u64> = HashMap::new();
+        let mut neighbors: HashMap<VertexId, Vec<VertexId>> = self.vertices.keys().map(|&id| (id, Vec::new())).collect();
+
+        for vertex in self.vertices.values() {
+            for edge in vertex.edges.iter().filter(|e| e.to != vertex.id && self.vertices.contains_key(&e.to)) {
+                if let Entry::Vacant(entry) = residual.entry((edge.to, vertex.id)) {
+                    entry.insert(0);
+                    neighbors.get_mut(&vertex.id).unwrap().push(edge.to);
+                    neighbors.get_mut(&edge.to).unwrap().push(vertex.id);
+                }
+                *residual.entry((vertex.id, edge.to)).or_insert(0) += u64::from(edge.weight);
+            }
+        }
+
+        if source == sink || !self.vertices.contains_key(&source) || !self.vertices.contains_key(&sink) {
+            return ResidualNetwork { flow: 0, capacities: residual, neighbors };
+        }
+
+        let mut flow: u64 = 0;
+
+        loop {
+            let mut via: HashMap<VertexId, VertexId> = HashMap::new();
+            let mut queue = VecDeque::from([source]);
+
+            while let Some(current) = queue.pop_front() {
+                if current == sink {
+                    break;
+                }
+
+                for &next in &neighbors[&current] {
+                    if next != source && !via.contains_key(&next) && residual[&(current, next)] > 0 {
+                        via.insert(next, current);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            if !via.contains_key(&sink) {
+                break;
+            }
+
+            let mut bottleneck = u64::MAX;
+            let mut current = sink;
+            while current != source {
+                let previous = via[&current];
+                bottleneck = bottleneck.min(residual[&(previous, current)]);
+                current = previous;
+            }
+
+            let mut current = sink;
+            while current != source {
+                let previous = via[&current];
+                *residual.get_mut(&(previous, current)).unwrap() -= bottleneck;
+                *residual.get_mut(&(current, previous)).unwrap() += bottleneck;
+                current = previous;
+            }
+
+            flow += bottleneck;
+        }
+
+        ResidualNetwork { flow: u32::try_from(flow).unwrap_or(u32::MAX), capacities: residual, neighbors }
+    }
+
+    /// Runs Dijkstra from `start` keeping every tight predecessor, one whose
+    /// edge reaches a vertex at its minimum distance. Zero-weight edges can tie
+    /// a vertex that is already settled, so those are recorded too, and a
+    /// zero-weight cycle then makes the predecessor graph cyclic. `order` is a
+    /// topological order of that graph, or `None` when it has such a cycle.
+    fn shortest_path_dag(&self, start: VertexId) -> ShortestPathDag {
+        let mut distances: HashMap<VertexId, u32> = HashMap::new();
+        let mut predecessors: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+        let mut visited: HashSet<VertexId> = HashSet::new();
+
+        let mut priority_queue = BinaryHeap::new();
+
+        if !self.vertices.contains_key(&start) {
+            return ShortestPathDag { distances, predecessors, order: Some(Vec::new()) };
+        }
+
+        distances.insert(start, 0);
+        priority_queue.push(State { vertex: start, cost: 0 });
+
+        while let Some(State { vertex: current_vertex, cost: current_distance }) = priority_queue.pop() {
+            if !visited.insert(current_vertex) {
+                continue;
+            }
+
+            if let Some(v) = self.vertices.get(&current_vertex) {
+                for neighbor in &v.edges {
+                    // No path from `start` re-enters it, so it never gets predecessors.
+                    if !self.vertices.contains_key(&neighbor.to) || neighbor.to == current_vertex || neighbor.to == start {
+                        continue;
+                    }
+
+                    let distance = current_distance + neighbor.weight;
+                    let known = *distances.get(&neighbor.to).unwrap_or(&u32::MAX);
+
+                    if distance < known {
+                        distances.insert(neighbor.to, distance);
+                        predecessors.insert(neighbor.to, vec![current_vertex]);
+                        priority_queue.push(State { vertex: neighbor.to, cost: distance });
+                    } else if distance == known {
+                        let preds = predecessors.entry(neighbor.to).or_default();
+
+                        if !preds.contains(&current_vertex) {
+                            preds.push(current_vertex);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Kahn's algorithm over predecessor edges; `start` is the only source.
+        let mut successors: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+        let mut pending: HashMap<VertexId, usize> = HashMap::new();
+
+        for (&vertex, preds) in &predecessors {
+            pending.insert(vertex, preds.len());
+
+            for &previous in preds {
+                successors.entry(previous).or_default().push(vertex);
+            }
+        }
+
+        let mut order = vec![start];
+        let mut next = 0;
+
+        while let Some(&vertex) = order.get(next) {
+            next += 1;
+
+            for &successor in successors.get(&vertex).into_iter().flatten() {
+                let remaining = pending.get_mut(&successor).unwrap();
+                *remaining -= 1;
+
+                if *remaining == 0 {
+                    order.push(successor);
+                }
+            }
+        }
+
+        let order = (order.len() == distances.len()).then_some(order);
+        ShortestPathDag { distances, predecessors, order }
+    }
+
+    pub fn all_pairs_shortest_paths(&self) -> HashMap<VertexId, HashMap<VertexId, u32>> {
+        self.vertices.keys().map(|&id| (id, self.dijkstra(id))).collect()
+    }
+
+    /// Largest shortest-path distance from `id` to any vertex it can reach.
+    pub fn eccentricity(&self, id: VertexId) -> Option<u32> {
+        self.dijkstra(id).into_values().max()
+    }
+
+    /// The vertex with the smallest eccentricity, ties broken by id.
+    pub fn center(&self) -> Option<VertexId> {
+        self.eccentricities().into_iter().map(|(id, e)| (e, id)).min().map(|(_, id)| id)
+    }
+
+    /// Vertices whose eccentricity equals the diameter, sorted.
+    pub fn periphery(&self) -> Vec<VertexId> {
+        let eccentricities = self.eccentricities();
+        let diameter = eccentricities.values().max().copied();
+        let mut periphery: Vec<VertexId> = eccentricities
+            .into_iter()
+            .filter(|&(_, e)| Some(e) == diameter)
+            .map(|(id, _)| id)
+            .collect();
+
+        periphery.sort();
+        periphery
+    }
+
+    /// The smallest eccentricity of any vertex.
+    pub fn graph_radius(&self) -> Option<u32> {
+        self.eccentricities().into_values().min()
+    }
+
+    /// The largest eccentricity of any vertex.
+    pub fn diameter(&self) -> Option<u32> {
+        self.eccentricities().into_values().max()
+    }
+
+    /// Double-sweep estimate of the diameter: two Dijkstra runs, from the
+    /// smallest id and then from the farthest vertex it reached. This is a
+    /// lower bound on `diameter`, and exact on trees.
+    pub fn approximate_diameter(&self) -> Option<u32> {
+        let farthest = |start: VertexId| {
+            self.dijkstra(start)
+                .into_iter()
+                .max_by_key(|&(id, distance)| (distance, Reverse(id)))
+                .unwrap()
+        };
+
+        let start = self.vertices.keys().min().copied()?;
+        let (u, _) = farthest(start);
+        let (_, distance) = farthest(u);
+
+        Some(distance)
+    }
+
+    fn eccentricities(&self) -> HashMap<VertexId, u32> {
+        self.all_pairs_shortest_paths()
+            .into_iter()
+            .map(|(id, distances)| (id, distances.into_values().max().unwrap_or(0)))
+            .collect()
+    }
+
+    /// Breadth-first reachability check that stops as soon as `goal` is seen.
+    pub fn path_exists(&self, start: VertexId, goal: VertexId) -> bool {
+        self.path_exists_with_visited(start, goal).0
+    }
+
+    /// Every vertex reachable from each vertex, itself included, so that
+    /// `closure[&a].contains(&b)` agrees with `path_exists(a, b)`.
+    pub fn transitive_closure(&self) -> HashMap<VertexId, HashSet<VertexId>> {
+        self.vertices
+            .keys()
+            .map(|&source| {
+                let mut reachable: HashSet<VertexId> = HashSet::from([source]);
+                let mut queue = VecDeque::from([source]);
+
+                while let Some(current) = queue.pop_front() {
+                    for edge in &self.vertices[&current].edges {
+                        if self.vertices.contains_key(&edge.to) && reachable.insert(edge.to) {
+                            queue.push_back(edge.to);
+                        }
+                    }
+                }
+
+                (source, reachable)
+            })
+            .collect()
+    }
+
+    /// Number of distinct loopless paths from `start` to `goal`, saturating at
+    /// `u64::MAX`. This enumerates every path by backtracking and takes
+    /// exponential time, so it is only practical on small graphs.
+    pub fn count_simple_paths(&self, start: VertexId, goal: VertexId) -> u64 {
+        fn count(graph: &Graph, current: VertexId, goal: VertexId, on_path: &mut HashSet<VertexId>) -> u64 {
+            if current == goal {
+                return 1;
+            }
+
+            let mut targets: Vec<VertexId> = graph.vertices[&current].edges.iter().map(|e| e.to).collect();
+            targets.sort();
+            targets.dedup();
+
+            let mut total = 0u64;
+            for next in targets {
+                if !graph.vertices.contains_key(&next) || !on_path.insert(next) {
+                    continue;
+                }
+
+                total = total.saturating_add(count(graph, next, goal, on_path));
+                on_path.remove(&next);
+            }
+
+            total
+        }
+
+        if !self.vertices.contains_key(&start) {
+            return 0;
+        }
+
+        count(self, start, goal, &mut HashSet::from([start]))
+    }
+
+    pub fn mutually_reachable(&self, a: VertexId, b: VertexId) -> bool {
+        self.path_exists(a, b) && self.path_exists(b, a)
+    }
+
+    fn path_exists_with_visited(&self, start: VertexId, goal: VertexId) -> (bool, usize) {
+        if !self.vertices.contains_key(&start) {
+            return (false, 0);
+        }
+
+        let mut visited: HashSet<VertexId> = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+
+        if start == goal {
+            return (true, visited.len());
+        }
+
+        while let Some(current) = queue.pop_front() {
+            for edge in &self.vertices[&current].edges {
+                if !self.vertices.contains_key(&edge.to) || !visited.insert(edge.to) {
+                    continue;
+                }
+
+                if edge.to == goal {
+                    return (true, visited.len());
+                }
+
+                queue.push_back(edge.to);
+            }
+        }
+
+        (false, visited.len())
+    }
+
+    /// Whether adding `from -> to` would close a directed cycle, i.e. whether
+    /// `to` already reaches `from`. A self-loop always does.
+    pub fn would_create_cycle(&self, from: VertexId, to: VertexId) -> bool {
+        from == to || self.path_exists(to, from)
+    }
+
+    /// Three-colour DFS that returns `false` as soon as a back edge is found.
+    pub fn is_dag(&self) -> bool {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Color {
+            Gray,
+            Black
+        }
+
+        let mut colors: HashMap<VertexId, Color> = HashMap::new();
+
+        for &root in self.vertices.keys() {
+            if colors.contains_key(&root) {
+                continue;
+            }
+
+            colors.insert(root, Color::Gray);
+            let mut stack = vec![(root, 0)];
+
+            while let Some(&mut (current, ref mut next_edge)) = stack.last_mut() {
+                let edges = &self.vertices[&current].edges;
+
+                if *next_edge == edges.len() {
+                    colors.insert(current, Color::Black);
+                    stack.pop();
+                    continue;
+                }
+
+                let to = edges[*next_edge].to;
+                *next_edge += 1;
+
+                match colors.get(&to) {
+                    Some(Color::Gray) => return false,
+                    Some(Color::Black) => {}
+                    None if self.vertices.contains_key(&to) => {
+                        colors.insert(to, Color::Gray);
+                        stack.push((to, 0));
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Weakly-connected components, each sorted by id and ordered by their
+    /// smallest vertex.
+    pub fn weakly_connected_components(&self) -> Vec<Vec<VertexId>> {
+        let adjacency = self.undirected_adjacency();
+        let mut seen: HashSet<VertexId> = HashSet::new();
+        let mut components = Vec::new();
+
+        for root in self.sorted_ids() {
+            if !seen.insert(root) {
+                continue;
+            }
+
+            let mut component = vec![root];
+            let mut stack = vec![root];
+
+            while let Some(current) = stack.pop() {
+                for &(next, _) in &adjacency[&current] {
+                    if seen.insert(next) {
+                        component.push(next);
+                        stack.push(next);
+                    }
+                }
+            }
+
+            component.sort();
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// Splits the graph into one `Graph` per weakly-connected component.
+    pub fn component_subgraphs(&self) -> Vec<Graph> {
+        self.weakly_connected_components()
+            .into_iter()
+            .map(|component| {
+                let members: HashSet<VertexId> = component.iter().copied().collect();
+
+                component
+                    .iter()
+                    .map(|id| {
+                        let vertex = &self.vertices[id];
+                        let edges = vertex.edges.iter().filter(|e| members.contains(&e.to)).copied().collect();
+                        Vertex { id: *id, edges }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Karp's minimum mean cycle: the cycle with the smallest average edge
+    /// weight, listed from its smallest vertex id without repeating it at the
+    /// end. Returns `None` if the graph is acyclic.
+    pub fn min_mean_cycle(&self) -> Option<(f64, Vec<VertexId>)> {
+        let ids = self.sorted_ids();
+        let n = ids.len();
+        let edges: Vec<(usize, usize, i128)> = ids
+            .iter()
+            .enumerate()
+            .flat_map(|(from, id)| {
+                let ids = &ids;
+                self.vertices[id].edges.iter().filter_map(move |e| {
+                    ids.binary_search(&e.to).ok().map(|to| (from, to, e.weight as i128))
+                })
+            })
+            .collect();
+
+        // walks[k][v]: cheapest walk of exactly k edges ending at v, from anywhere.
+        let mut walks: Vec<Vec<Option<i128>>> = vec![vec![Some(0); n]];
+        for k in 1..=n {
+            let mut row = vec![None; n];
+            for &(from, to, weight) in &edges {
+                if let Some(d) = walks[k - 1][from] {
+                    row[to] = Some(row[to].map_or(d + weight, |best: i128| best.min(d + weight)));
+                }
+            }
+            walks.push(row);
+        }
+
+        // The minimum mean is min over v of max over k of (D_n(v) - D_k(v)) / (n - k),
+        // kept as an exact fraction (numerator, denominator).
+        let mut best: Option<(i128, i128)> = None;
+        for (v, full) in walks[n].iter().enumerate() {
+            let full = match *full {
+                Some(full) => full,
+                None => continue,
+            };
+
+            let worst = walks[..n]
+                .iter()
+                .enumerate()
+                .filter_map(|(k, row)| row[v].map(|d| (full - d, (n - k) as i128)))
+                .max_by(|a, b| (a.0 * b.1).cmp(&(b.0 * a.1)))
+                .unwrap();
+
+            if best.is_none_or(|b| worst.0 * b.1 < b.0 * worst.1) {
+                best = Some(worst);
+            }
+        }
+
+        let (numerator, denominator) = best?;
+
+        // Reweighting by the mean leaves no negative cycles, and the minimum mean
+        // cycles become exactly the zero-weight ones, which are made of edges
+        // that are tight under the shortest-distance potential.
+        let reduced = |weight: i128| weight * denominator - numerator;
+        let mut potential = vec![0i128; n];
+        for _ in 0..n {
+            for &(from, to, weight) in &edges {
+                potential[to] = potential[to].min(potential[from] + reduced(weight));
+            }
+        }
+
+        let mut tight: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for &(from, to, weight) in &edges {
+            if potential[from] + reduced(weight) == potential[to] {
+                tight[from].push(to);
+            }
+        }
+
+        let cycle = find_cycle(&tight)?;
+        let first = (0..cycle.len()).min_by_key(|&i| ids[cycle[i]]).unwrap();
+        let cycle = cycle[first..].iter().chain(&cycle[..first]).map(|&i| ids[i]).collect();
+
+        Some((numerator as f64 / denominator as f64, cycle))
+    }
+
+    /// Whether `other` is the same weighted directed graph up to renaming of
+    /// vertices, found by backtracking over degree-compatible mappings. The
+    /// search takes exponential time in the worst case, so it is meant for
+    /// small graphs such as test fixtures; large regular graphs can be slow.
+    pub fn is_isomorphic(&self, other: &Graph) -> bool {
+        fn cells(graph: &Graph) -> Vec<Vec<Vec<u32>>> {
+            let ids = graph.sorted_ids();
+            let mut matrix = vec![vec![Vec::new(); ids.len()]; ids.len()];
+
+            for (row, id) in ids.iter().enumerate() {
+                for edge in &graph.vertices[id].edges {
+                    if let Ok(column) = ids.binary_search(&edge.to) {
+                        matrix[row][column].push(edge.weight);
+                    }
+                }
+            }
+            for cell in matrix.iter_mut().flatten() {
+                cell.sort();
+            }
+
+            matrix
+        }
+
+        fn degrees(matrix: &[Vec<Vec<u32>>]) -> Vec<(usize, usize)> {
+            (0..matrix.len())
+                .map(|v| {
+                    let out: usize = matrix[v].iter().map(Vec::len).sum();
+                    let incoming: usize = matrix.iter().map(|row| row[v].len()).sum();
+                    (out, incoming)
+                })
+                .collect()
+        }
+
+        fn extend(left: &[Vec<Vec<u32>>], right: &[Vec<Vec<u32>>], left_degrees: &[(usize, usize)], right_degrees: &[(usize, usize)], mapping: &mut Vec<usize>, used: &mut [bool]) -> bool {
+            let next = mapping.len();
+
+            if next == left.len() {
+                return true;
+            }
+
+            for candidate in 0..right.len() {
+                if used[candidate] || left_degrees[next] != right_degrees[candidate] || left[next][next] != right[candidate][candidate] {
+                    continue;
+                }
+
+                let consistent = mapping.iter().enumerate().all(|(earlier, &image)| {
+                    left[earlier][next] == right[image][candidate] && left[next][earlier] == right[candidate][image]
+                });
+
+                if consistent {
+                    mapping.push(candidate);
+                    used[candidate] = true;
+
+                    if extend(left, right, left_degrees, right_degrees, mapping, used) {
+                        return true;
+                    }
+
+                    mapping.pop();
+                    used[candidate] = false;
+                }
+            }
+
+            false
+        }
+
+        if self.vertices.len() != other.vertices.len() {
+            return false;
+        }
+
+        let (left, right) = (cells(self), cells(other));
+        let (left_degrees, right_degrees) = (degrees(&left), degrees(&right));
+
+        let mut sorted_left = left_degrees.clone();
+        let mut sorted_right = right_degrees.clone();
+        sorted_left.sort();
+        sorted_right.sort();
+
+        if sorted_left != sorted_right {
+            return false;
+        }
+
+        let mut used = vec![false; right.len()];
+        extend(&left, &right, &left_degrees, &right_degrees, &mut Vec::new(), &mut used)
+    }
+
+    /// Kahn's topological order, taking the smallest available id first.
+    /// Fails with `GraphError::NotADag` if the graph has a cycle.
+    pub fn topological_sort(&self) -> Result<Vec<VertexId>, GraphError> {
+        let mut in_degree: HashMap<VertexId, usize> = self.vertices.keys().map(|&id| (id, 0)).collect();
+
+        for edge in self.vertices.values().flat_map(|v| &v.edges) {
+            if let Some(degree) = in_degree.get_mut(&edge.to) {
+                *degree += 1;
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<VertexId>> = in_degree
+            .iter()
+            .filter(|&(_, &degree)| degree == 0)
+            .map(|(&id, _)| Reverse(id))
+            .collect();
+        let mut order = Vec::with_capacity(self.vertices.len());
+
+        while let Some(Reverse(current)) = ready.pop() {
+            order.push(current);
+
+            for edge in &self.vertices[&current].edges {
+                if let Some(degree) = in_degree.get_mut(&edge.to) {
+                    *degree -= 1;
+
+                    if *degree == 0 {
+                        ready.push(Reverse(edge.to));
+                    }
+                }
+            }
+        }
+
+        if order.len() == self.vertices.len() {
+            Ok(order)
+        } else {
+            Err(GraphError::NotADag)
+        }
+    }
+
+    /// Heaviest path from `start` to `goal` in a DAG, by dynamic programming
+    /// over a topological order. `Ok(None)` if `goal` is unreachable.
+    pub fn longest_path_dag(&self, start: VertexId, goal: VertexId) -> Result<Option<(u32, Vec<VertexId>)>, GraphError> {
+        let order = self.topological_sort()?;
+        let mut best: HashMap<VertexId, u32> = HashMap::new();
+        let mut predecessors: HashMap<VertexId, VertexId> = HashMap::new();
+
+        if self.vertices.contains_key(&start) {
+            best.insert(start, 0);
+        }
+
+        for current in order {
+            let current_distance = match best.get(&current) {
+                Some(&distance) => distance,
+                None => continue,
+            };
+
+            for edge in &self.vertices[&current].edges {
+                let distance = current_distance + edge.weight;
+
+                if self.vertices.contains_key(&edge.to) && best.get(&edge.to).is_none_or(|&known| distance > known) {
+                    best.insert(edge.to, distance);
+                    predecessors.insert(edge.to, current);
+                }
+            }
+        }
+
+        Ok(best.get(&goal).map(|&cost| (cost, reconstruct_path(&predecessors, start, goal))))
+    }
+
+    /// Prim's minimum spanning tree over the undirected view of the graph, where
+    /// an edge in either direction connects its endpoints at the cheaper weight.
+    /// Returns `None` if the graph is disconnected.
+    pub fn prim_mst(&self) -> Option<Vec<(VertexId, VertexId, u32)>> {
+        let adjacency = self.undirected_adjacency();
+        let mut tree = Vec::new();
+
+        let root = match adjacency.keys().min() {
+            Some(&root) => root,
+            None => return Some(tree),
+        };
+
+        let mut in_tree: HashSet<VertexId> = HashSet::new();
+        let mut frontier = BinaryHeap::new();
+
+        in_tree.insert(root);
+        for &(to, weight) in &adjacency[&root] {
+            frontier.push(Reverse((weight, root, to)));
+        }
+
+        while let Some(Reverse((weight, from, to))) = frontier.pop() {
+            if !in_tree.insert(to) {
+                continue;
+            }
+
+            tree.push((from, to, weight));
+
+            for &(next, next_weight) in &adjacency[&to] {
+                if !in_tree.contains(&next) {
+                    frontier.push(Reverse((next_weight, to, next)));
+                }
+            }
+        }
+
+        if in_tree.len() == adjacency.len() {
+            Some(tree)
+        } else {
+            None
+        }
+    }
+
+    /// Spanning tree minimising its heaviest edge. Every minimum spanning tree
+    /// is also a minimum bottleneck spanning tree, so this is Prim's tree.
+    pub fn min_bottleneck_spanning_tree(&self) -> Option<Vec<(VertexId, VertexId, u32)>> {
+        self.prim_mst()
+    }
+
+    /// k-core number of every vertex on the undirected view, found by
+    /// repeatedly peeling off a vertex of minimum remaining degree.
+    pub fn core_numbers(&self) -> HashMap<VertexId, usize> {
+        let adjacency = self.undirected_adjacency();
+        let mut degrees: HashMap<VertexId, usize> = adjacency.iter().map(|(&id, n)| (id, n.len())).collect();
+        let mut queue: BTreeSet<(usize, VertexId)> = degrees.iter().map(|(&id, &d)| (d, id)).collect();
+        let mut cores: HashMap<VertexId, usize> = HashMap::new();
+        let mut k = 0;
+
+        while let Some((degree, vertex)) = queue.pop_first() {
+            k = k.max(degree);
+            cores.insert(vertex, k);
+
+            for &(neighbor, _) in &adjacency[&vertex] {
+                if cores.contains_key(&neighbor) {
+                    continue;
+                }
+
+                let current = degrees.get_mut(&neighbor).unwrap();
+                queue.remove(&(*current, neighbor));
+                *current -= 1;
+                queue.insert((*current, neighbor));
+            }
+        }
+
+        cores
+    }
+
+    /// Kruskal's minimum spanning tree over the undirected view of the graph.
+    /// On a disconnected graph this returns a spanning forest with one tree per
+    /// weakly-connected component.
+    pub fn kruskal_mst(&self) -> Vec<(VertexId, VertexId, u32)> {
+        let ids = self.sorted_ids();
+        let mut components = UnionFind::new(ids.len());
+        let mut forest = Vec::new();
+
+        for (from, to, weight) in self.edges_by_weight() {
+            let (a, b) = match (ids.binary_search(&from), ids.binary_search(&to)) {
+                (Ok(a), Ok(b)) => (a, b),
+                _ => continue,
+            };
+
+            if components.union(a, b) {
+                forest.push((from, to, weight));
+            }
+        }
+
+        forest
+    }
+
+    fn undirected_adjacency(&self) -> HashMap<VertexId, Vec<(VertexId, u32)>> {
+        let mut weights: HashMap<(VertexId, VertexId), u32> = HashMap::new();
+
+        for vertex in self.vertices.values() {
+            for edge in &vertex.edges {
+                if edge.to == vertex.id || !self.vertices.contains_key(&edge.to) {
+                    continue;
+                }
+
+                for key in [(vertex.id, edge.to), (edge.to, vertex.id)] {
+                    let weight = weights.entry(key).or_insert(edge.weight);
+                    *weight = (*weight).min(edge.weight);
+                }
+            }
+        }
+
+        let mut adjacency: HashMap<VertexId, Vec<(VertexId, u32)>> =
+            self.vertices.keys().map(|&id| (id, Vec::new())).collect();
+
+        for ((from, to), weight) in weights {
+            adjacency.get_mut(&from).unwrap().push((to, weight));
+        }
+
+        adjacency
+    }
+
+    pub fn dijkstra_heap(&mut self, start: Vertex) {
+        let mut distances: HashMap<VertexId, u32> = HashMap::new();
+        let mut visited: HashSet<VertexId> = HashSet::new();
+
+        let mut priority_queue = BinaryHeap::new();
+
+        distances.insert(start.id, 0);
+        priority_queue.push(State { vertex: start.id, cost: 0 });
+
+        while let Some(State { vertex: current_vertex, cost: current_distance }) = priority_queue.pop() {
+            if !visited.insert(current_vertex) {
+                continue;
+            }
+
+            if let Some(v) = self.vertices.get(&current_vertex) {
+                for neighbor in &v.edges {
+                    if let Some(next) = self.vertices.get(&neighbor.to) {
+                        let distance = current_distance + neighbor.weight;
+
+                        if distance < *distances.get(&neighbor.to).unwrap_or(&u32::MAX) {
+                            distances.insert(neighbor.to, distance);
+                            priority_queue.push(State { vertex: next.id, cost: distance });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn dijkstra_no_heap(&mut self, start: Vertex) {
+        let mut distances: HashMap<VertexId, u32> = HashMap::new();
+        let mut visited: HashSet<VertexId> = HashSet::new();
+
+        distances.insert(start.id, 0);
+
+        let mut current_vertex = start.id;
+        let graph_len = self.vertices.keys().len();
+
+        while visited.len() < graph_len {
+            visited.insert(current_vertex);
+            let current_distance = *distances.get(&current_vertex).unwrap_or(&u32::MAX);
+
+            if let Some(v) = self.vertices.get(&current_vertex) {
+                for neighbor in &v.edges {
+                    let distance = current_distance + neighbor.weight;
+
+                    if distance < *distances.get(&neighbor.to).unwrap_or(&u32::MAX) {
+                        distances.insert(neighbor.to, distance);
+                    }
+                }
+            }
+
+            let next_vertex = self.vertices
+                .iter()
+                .filter(|(_, v)| !visited.contains(&v.id))
+                .min_by_key(|(_, v)| distances.get(&v.id).unwrap_or(&u32::MAX))
+                .map(|(_, v)| v.clone());
+
+            match next_vertex {
+                Some(v) => current_vertex = v.id,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Saved state of a `Graph`, taken by `Graph::snapshot`.
+#[derive(Debug, Clone)]
+pub struct GraphSnapshot(Graph);
+
+/// A node of the shortest-path tree returned by `Graph::spt_as_tree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SptNode {
+    pub id: VertexId,
+    pub distance: u32,
+    pub children: Vec<SptNode>
+}
+
+/// Dense adjacency-matrix representation. Rows and columns follow the sorted
+/// vertex ids. A matrix holds one weight per ordered pair, so converting from
+/// a `Graph` collapses parallel edges to their minimum weight, and converting
+/// back yields edge lists ordered by target id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixGraph {
+    pub ids: Vec<VertexId>,
+    pub weights: Vec<Option<u32>>
+}
+
+impl MatrixGraph {
+    pub fn weight(&self, from: VertexId, to: VertexId) -> Option<u32> {
+        let row = self.ids.binary_search(&from).ok()?;
+        let column = self.ids.binary_search(&to).ok()?;
+
+        self.weights[row * self.ids.len() + column]
+    }
+}
+
+impl From<&Graph> for MatrixGraph {
+    fn from(graph: &Graph) -> Self {
+        let ids = graph.sorted_ids();
+        let n = ids.len();
+        let mut weights = vec![None; n * n];
+
+        for (row, id) in ids.iter().enumerate() {
+            for edge in &graph.vertices[id].edges {
+                if let Ok(column) = ids.binary_search(&edge.to) {
+                    let cell: &mut Option<u32> = &mut weights[row * n + column];
+                    *cell = Some(cell.map_or(edge.weight, |w| w.min(edge.weight)));
+                }
+            }
+        }
+
+        MatrixGraph { ids, weights }
+    }
+}
+
+impl From<&MatrixGraph> for Graph {
+    fn from(matrix: &MatrixGraph) -> Self {
+        let n = matrix.ids.len();
+
+        matrix.ids
+            .iter()
+            .enumerate()
+            .map(|(row, &id)| Vertex {
+                id,
+                edges: matrix.weights[row * n..(row + 1) * n]
+                    .iter()
+                    .zip(&matrix.ids)
+                    .filter_map(|(weight, &to)| weight.map(|weight| Edge { to, weight }))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Reusable A* buffers for running many searches without reallocating. The
+/// open heap and score maps are cleared, not freed, between searches.
+#[derive(Default)]
+pub struct AStarContext {
+    open: BinaryHeap<State>,
+    g_score: HashMap<VertexId, u32>,
+    came_from: HashMap<VertexId, VertexId>
+}
+
+impl AStarContext {
+    pub fn new() -> Self {
+        AStarContext::default()
+    }
+
+    /// A* search guided by `heuristic`, which must never overestimate the
+    /// remaining distance to `goal` for the result to be optimal.
+    pub fn search<H: Fn(VertexId) -> u32>(&mut self, graph: &Graph, start: VertexId, goal: VertexId, heuristic: H) -> Option<(u32, Vec<VertexId>)> {
+        self.open.clear();
+        self.g_score.clear();
+        self.came_from.clear();
+
+        if !graph.vertices.contains_key(&start) {
+            return None;
+        }
+
+        self.g_score.insert(start, 0);
+        self.open.push(State { vertex: start, cost: heuristic(start) });
+
+        while let Some(State { vertex: current_vertex, cost: estimate }) = self.open.pop() {
+            let current_distance = self.g_score[&current_vertex];
+
+            if estimate > current_distance + heuristic(current_vertex) {
+                continue;
+            }
+
+            if current_vertex == goal {
+                return Some((current_distance, reconstruct_path(&self.came_from, start, goal)));
+            }
+
+            for neighbor in &graph.vertices[&current_vertex].edges {
+                if !graph.vertices.contains_key(&neighbor.to) {
+                    continue;
+                }
+
+                let distance = current_distance + neighbor.weight;
+
+                if distance < *self.g_score.get(&neighbor.to).unwrap_or(&u32::MAX) {
+                    self.g_score.insert(neighbor.to, distance);
+                    self.came_from.insert(neighbor.to, current_vertex);
+                    self.open.push(State { vertex: neighbor.to, cost: distance + heuristic(neighbor.to) });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A `Graph` with planar coordinates attached to its vertices, for geographic
+/// routing with a straight-line A* heuristic.
+#[derive(Default)]
+pub struct SpatialGraph {
+    pub graph: Graph,
+    pub coordinates: HashMap<VertexId, (f64, f64)>
+}
+
+impl SpatialGraph {
+    pub fn new(graph: Graph) -> Self {
+        SpatialGraph {
+            graph,
+            coordinates: HashMap::new()
+        }
+    }
+
+    pub fn set_coord(&mut self, id: VertexId, coord: (f64, f64)) {
+        self.coordinates.insert(id, coord);
+    }
+
+    pub fn get_coord(&self, id: VertexId) -> Option<(f64, f64)> {
+        self.coordinates.get(&id).copied()
+    }
+
+    /// Straight-line distance to `goal`, rounded down, for use with `astar`.
+    /// It is only admissible if edge weights are never shorter than the
+    /// coordinate distance between their endpoints, i.e. both are in compatible
+    /// units. Vertices without coordinates get an estimate of 0.
+    pub fn straight_line_heuristic(&self, goal: VertexId) -> impl Fn(VertexId) -> u32 + '_ {
+        let target = self.get_coord(goal);
+
+        move |id| match (self.get_coord(id), target) {
+            (Some((x, y)), Some((gx, gy))) => (x - gx).hypot(y - gy).floor() as u32,
+            _ => 0,
+        }
+    }
+
+    pub fn astar(&self, start: VertexId, goal: VertexId) -> Option<(u32, Vec<VertexId>)> {
+        self.graph.astar(start, goal, self.straight_line_heuristic(goal))
+    }
+}
+
+/// A shortest-path query with optional constraints, built by `Graph::query`
+/// and evaluated in a single Dijkstra pass by `run`.
+pub struct Query<'a> {
+    graph: &'a Graph,
+    start: VertexId,
+    goal: Option<VertexId>,
+    blocked: HashSet<VertexId>,
+    max_distance: Option<u32>,
+    max_hops: Option<usize>
+}
+
+impl<'a> Query<'a> {
+    /// Stops the search once `goal` is settled.
+    pub fn to(mut self, goal: VertexId) -> Self {
+        self.goal = Some(goal);
+        self
+    }
+
+    /// Never enters a vertex in `blocked`. A blocked start or goal yields no path.
+    pub fn avoiding(mut self, blocked: &HashSet<VertexId>) -> Self {
+        self.blocked.extend(blocked);
+        self
+    }
+
+    /// Ignores vertices farther than `max_distance` from the start.
+    pub fn within(mut self, max_distance: u32) -> Self {
+        self.max_distance = Some(max_distance);
+        self
+    }
+
+    /// Only considers paths of at most `max_hops` edges.
+    pub fn max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+
+    pub fn run(&self) -> QueryResult {
+        // Search states are (vertex, hops used). Without a hop limit every state
+        // has 0 hops and this is plain Dijkstra. A state is skipped once its
+        // vertex has been settled with no more hops, since that path is both
+        // cheaper and shorter.
+        type HopState = (VertexId, usize);
+
+        let graph = self.graph;
+        let mut result = QueryResult {
+            start: self.start,
+            goal: self.goal,
+            distances: HashMap::new(),
+            arrivals: HashMap::new(),
+            parents: HashMap::new()
+        };
+
+        if !graph.vertices.contains_key(&self.start) || self.blocked.contains(&self.start) {
+            return result;
+        }
+
+        let mut costs: HashMap<HopState, u32> = HashMap::new();
+        let mut fewest_hops: HashMap<VertexId, usize> = HashMap::new();
+        let mut priority_queue = BinaryHeap::new();
+
+        costs.insert((self.start, 0), 0);
+        priority_queue.push(Reverse((0, self.start, 0)));
+
+        while let Some(Reverse((current_distance, current_vertex, hops))) = priority_queue.pop() {
+            if costs[&(current_vertex, hops)] < current_distance {
+                continue;
+            }
+
+            if fewest_hops.get(&current_vertex).is_some_and(|&settled| settled <= hops) {
+                continue;
+            }
+
+            fewest_hops.insert(current_vertex, hops);
+            if let Entry::Vacant(entry) = result.distances.entry(current_vertex) {
+                entry.insert(current_distance);
+                result.arrivals.insert(current_vertex, hops);
+            }
+
+            if self.goal == Some(current_vertex) {
+                break;
+            }
+
+            let next_hops = match self.max_hops {
+                Some(limit) if hops >= limit => continue,
+                Some(_) => hops + 1,
+                None => 0,
+            };
+
+            for neighbor in &graph.vertices[&current_vertex].edges {
+                if !graph.vertices.contains_key(&neighbor.to) || self.blocked.contains(&neighbor.to) {
+                    continue;
+                }
+
+                let distance = current_distance + neighbor.weight;
+                let next = (neighbor.to, next_hops);
+
+                if self.max_distance.is_some_and(|limit| distance > limit) {
+                    continue;
+                }
+
+                if distance < *costs.get(&next).unwrap_or(&u32::MAX) {
+                    costs.insert(next, distance);
+                    result.parents.insert(next, (current_vertex, hops));
+                    priority_queue.push(Reverse((distance, neighbor.to, next_hops)));
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Outcome of `Query::run`: the distances of every vertex the search settled
+/// and the routes to them.
+pub struct QueryResult {
+    pub distances: HashMap<VertexId, u32>,
+    start: VertexId,
+    goal: Option<VertexId>,
+    arrivals: HashMap<VertexId, usize>,
+    parents: HashMap<(VertexId, usize), (VertexId, usize)>
+}
+
+impl QueryResult {
+    /// Cost and route to the query's goal, if one was set and reached.
+    pub fn path(&self) -> Option<(u32, Vec<VertexId>)> {
+        self.path_to(self.goal?)
+    }
+
+    /// Cost and route to any settled vertex.
+    pub fn path_to(&self, goal: VertexId) -> Option<(u32, Vec<VertexId>)> {
+        let cost = *self.distances.get(&goal)?;
+        let mut state = (goal, self.arrivals[&goal]);
+        let mut path = vec![goal];
+
+        while state != (self.start, 0) {
+            state = self.parents[&state];
+            path.push(state.0);
+        }
+
+        path.reverse();
+        Some((cost, path))
+    }
+}
+
+#[cfg(feature = "dimacs")]
+impl Graph {
+    /// Parses a DIMACS shortest-path (`.gr`) file. DIMACS node `n` becomes
+    /// `VertexId(char::from_u32(n))`, so ids in the surrogate range
+    /// (0xD800..=0xDFFF) or above `char::MAX` are rejected.
+    pub fn from_dimacs<R: std::io::Read>(reader: R) -> Result<Graph, GraphError> {
+        use std::io::BufRead;
+
+        let parse_error = |line: usize, message: String| GraphError::Parse { line, message };
+        let mut graph = Graph::new();
+        let mut header: Option<(u32, usize)> = None;
+        let mut arcs = 0;
+        let mut last_line = 0;
+
+        for (index, line) in std::io::BufReader::new(reader).lines().enumerate() {
+            let number = index + 1;
+            let line = line.map_err(|e| parse_error(number, e.to_string()))?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            last_line = number;
+
+            let numbers = |fields: &[&str]| -> Result<Vec<u32>, GraphError> {
+                fields
+                    .iter()
+                    .map(|f| f.parse::<u32>().map_err(|_| parse_error(number, format!("invalid number `{}`", f))))
+                    .collect()
+            };
+
+            match fields.first() {
+                None | Some(&"c") => continue,
+                Some(&"p") => {
+                    if header.is_some() {
+                        return Err(parse_error(number, "duplicate problem line".to_string()));
+                    }
+                    if fields.len() != 4 || fields[1] != "sp" {
+                        return Err(parse_error(number, "expected `p sp <vertices> <arcs>`".to_string()));
+                    }
+
+                    let counts = numbers(&fields[2..])?;
+
+                    // Checked before reserving so a hostile count cannot exhaust memory.
+                    if counts[0] > MAX_DIMACS_NODE {
+                        return Err(parse_error(number, format!("vertex count {} exceeds {}", counts[0], MAX_DIMACS_NODE)));
+                    }
+
+                    graph.vertices.reserve(counts[0] as usize);
+
+                    for node in 1..=counts[0] {
+                        let id = dimacs_vertex(node).ok_or_else(|| parse_error(number, format!("node {} has no vertex id", node)))?;
+                        graph.add_vertex(Vertex { id, edges: Vec::new() });
+                    }
+
+                    header = Some((counts[0], counts[1] as usize));
+                }
+                Some(&"a") => {
+                    let (vertex_count, _) = header.ok_or_else(|| parse_error(number, "arc before problem line".to_string()))?;
+
+                    if fields.len() != 4 {
+                        return Err(parse_error(number, "expected `a <from> <to> <weight>`".to_string()));
+                    }
+
+                    let values = numbers(&fields[1..])?;
+
+                    for &node in &values[..2] {
+                        if node == 0 || node > vertex_count {
+                            return Err(parse_error(number, format!("node {} out of range 1..={}", node, vertex_count)));
+                        }
+                    }
+
+                    graph.add_edge(dimacs_vertex(values[0]).unwrap(), dimacs_vertex(values[1]).unwrap(), values[2]);
+                    arcs += 1;
+                }
+                Some(other) => return Err(parse_error(number, format!("unknown line type `{}`", other))),
+            }
+        }
+
+        match header {
+            None => Err(parse_error(last_line, "missing problem line".to_string())),
+            Some((_, expected)) if expected != arcs => {
+                Err(parse_error(last_line, format!("expected {} arcs, found {}", expected, arcs)))
+            }
+            Some(_) => Ok(graph),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+pub const BINARY_FORMAT_VERSION: u8 = 1;
+
+#[cfg(feature = "binary")]
+impl Graph {
+    /// Writes the graph as a compact little-endian edge list: a version byte,
+    /// the vertex count, then for each vertex (in id order) its id, its edge
+    /// count and its `(to, weight)` pairs, all as `u32`.
+    pub fn write_binary<W: std::io::Write>(&self, w: W) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let mut w = std::io::BufWriter::new(w);
+
+        w.write_all(&[BINARY_FORMAT_VERSION])?;
+        w.write_all(&(self.vertices.len() as u32).to_le_bytes())?;
+
+        for vertex in self {
+            w.write_all(&(vertex.id.0 as u32).to_le_bytes())?;
+            w.write_all(&(vertex.edges.len() as u32).to_le_bytes())?;
+
+            for edge in &vertex.edges {
+                w.write_all(&(edge.to.0 as u32).to_le_bytes())?;
+                w.write_all(&edge.weight.to_le_bytes())?;
+            }
+        }
+
+        w.flush()
+    }
+
+    /// Reads a graph written by `write_binary`.
+    pub fn read_binary<R: std::io::Read>(r: R) -> std::io::Result<Graph> {
+        use std::io::{Error, ErrorKind, Read};
+
+        let mut r = std::io::BufReader::new(r);
+        let read_u32 = |r: &mut std::io::BufReader<R>| -> std::io::Result<u32> {
+            let mut bytes = [0; 4];
+            r.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let read_id = |value: u32| {
+            char::from_u32(value)
+                .map(VertexId)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("invalid vertex id {:#x}", value)))
+        };
+
+        let mut version = [0; 1];
+        r.read_exact(&mut version)?;
+
+        if version[0] != BINARY_FORMAT_VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported format version {}", version[0])));
+        }
+
+        // Counts come from untrusted input, so only a bounded amount is
+        // reserved up front and the rest grows as data actually arrives.
+        const RESERVE_LIMIT: u32 = 1 << 16;
+        const SCALAR_VALUES: u32 = 0x11_0000 - 0x800;
+
+        let vertex_count = read_u32(&mut r)?;
+
+        if vertex_count > SCALAR_VALUES {
+            return Err(Error::new(ErrorKind::InvalidData, format!("vertex count {} exceeds the char range", vertex_count)));
+        }
+
+        let mut graph = Graph::new();
+        graph.vertices.reserve(vertex_count.min(RESERVE_LIMIT) as usize);
+
+        for _ in 0..vertex_count {
+            let id = read_id(read_u32(&mut r)?)?;
+            let edge_count = read_u32(&mut r)?;
+            let mut edges = Vec::with_capacity(edge_count.min(RESERVE_LIMIT) as usize);
+
+            for _ in 0..edge_count {
+                let to = read_id(read_u32(&mut r)?)?;
+                edges.push(Edge { to, weight: read_u32(&mut r)? });
+            }
+
+            graph.add_vertex(Vertex { id, edges });
+        }
+
+        Ok(graph)
+    }
+}
+
+/// Largest DIMACS node number with a vertex id: nodes map straight to `char`s,
+/// so they must stop short of the surrogate range.
+#[cfg(feature = "dimacs")]
+const MAX_DIMACS_NODE: u32 = 0xd7ff;
+
+#[cfg(feature = "dimacs")]
+fn dimacs_vertex(node: u32) -> Option<VertexId> {
+    char::from_u32(node).map(VertexId)
+}
+
+impl Extend<Vertex> for Graph {
+    fn extend<I: IntoIterator<Item = Vertex>>(&mut self, iter: I) {
+        for vertex in iter {
+            self.add_vertex(vertex);
+        }
+    }
+}
+
+impl FromIterator<Vertex> for Graph {
+    fn from_iter<I: IntoIterator<Item = Vertex>>(iter: I) -> Self {
+        let mut graph = Graph::new();
+        graph.extend(iter);
+        graph
+    }
+}
+
+impl<'a> IntoIterator for &'a Graph {
+    type Item = &'a Vertex;
+    type IntoIter = std::vec::IntoIter<&'a Vertex>;
+
+    /// Iterates the vertices in sorted id order.
+    fn into_iter(self) -> Self::IntoIter {
+        let mut vertices: Vec<&Vertex> = self.vertices.values().collect();
+        vertices.sort_by_key(|v| v.id);
+        vertices.into_iter()
+    }
+}
+
+/// The `index`-th vertex id counting up from `'a'`, skipping the surrogate
+/// code points that are not valid `char`s.
+fn indexed_vertex(index: usize) -> VertexId {
+    let mut code = 'a' as u32 + index as u32;
+
+    if code >= 0xD800 {
+        code += 0x800;
+    }
+
+    VertexId(char::from_u32(code).expect("vertex index out of char range"))
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parent: (0..size).collect(),
+            rank: vec![0; size]
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        let mut current = x;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+
+        root
+    }
+
+    /// Merges the sets holding `a` and `b`; returns `false` if already joined.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+
+        if a == b {
+            return false;
+        }
+
+        match self.rank[a].cmp(&self.rank[b]) {
+            Ordering::Less => self.parent[a] = b,
+            Ordering::Greater => self.parent[b] = a,
+            Ordering::Equal => {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+
+        true
+    }
+}
+
+/// Finds any directed cycle in an index-based adjacency list.
+fn find_cycle(adjacency: &[Vec<usize>]) -> Option<Vec<usize>> {
+    let mut state = vec![0u8; adjacency.len()];
+
+    for root in 0..adjacency.len() {
+        if state[root] != 0 {
+            continue;
+        }
+
+        let mut stack = vec![(root, 0)];
+        state[root] = 1;
+
+        while let Some(&mut (current, ref mut next)) = stack.last_mut() {
+            if *next == adjacency[current].len() {
+                state[current] = 2;
+                stack.pop();
+                continue;
+            }
+
+            let to = adjacency[current][*next];
+            *next += 1;
+
+            match state[to] {
+                0 => {
+                    state[to] = 1;
+                    stack.push((to, 0));
+                }
+                1 => {
+                    let start = stack.iter().position(|&(v, _)| v == to).unwrap();
+                    return Some(stack[start..].iter().map(|&(v, _)| v).collect());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
+fn reconstruct_path(predecessors: &HashMap<VertexId, VertexId>, start: VertexId, goal: VertexId) -> Vec<VertexId> {
+    let mut path = vec![goal];
+    let mut current = goal;
+
+    while current != start {
+        current = predecessors[&current];
+        path.push(current);
+    }
+
+    path.reverse();
+    path
+}
+
+/// Walks every simple path from `start` to `goal` through `predecessors`,
+/// handing each to `visit` goal first. Stops early once `visit` returns
+/// `false`. The on-path check keeps zero-weight cycles from repeating vertices.
+fn for_each_tight_path<F: FnMut(&[VertexId]) -> bool>(predecessors: &HashMap<VertexId, Vec<VertexId>>, start: VertexId, goal: VertexId, mut visit: F) {
+    let mut path = vec![goal];
+    let mut cursors = vec![0];
+    let mut on_path: HashSet<VertexId> = HashSet::from([goal]);
+
+    while let Some(&current) = path.last() {
+        let cursor = cursors.last_mut().unwrap();
+        let previous = predecessors.get(&current).and_then(|preds| preds.get(*cursor)).copied();
+
+        if current == start && *cursor == 0 && !visit(&path) {
+            return;
+        }
+
+        match previous {
+            Some(previous) if current != start => {
+                *cursor += 1;
+
+                if on_path.insert(previous) {
+                    path.push(previous);
+                    cursors.push(0);
+                }
+            }
+            _ => {
+                on_path.remove(&current);
+                path.pop();
+                cursors.pop();
+            }
+        }
+    }
+}
+
+struct ShortestPathDag {
+    distances: HashMap<VertexId, u32>,
+    predecessors: HashMap<VertexId, Vec<VertexId>>,
+    order: Option<Vec<VertexId>>
+}
+
+struct ResidualNetwork {
+    flow: u32,
+    capacities: HashMap<(VertexId, VertexId), u64>,
+    neighbors: HashMap<VertexId, Vec<VertexId>>
+}
+
+#[derive(Eq, PartialEq)]
+struct State {
+    vertex: VertexId,
+    cost: u32
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.cmp(&self.cost).then_with(|| other.vertex.cmp(&self.vertex))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        assert_eq!(true, true);
+    }
+
+    #[test]
+    fn try_from_edges_accepts_valid_edge_list() {
+        let graph = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 2), ('a', 'b', 1)]).unwrap();
+
+        assert_eq!(graph.vertices.len(), 3);
+        assert_eq!(graph.vertices[&VertexId('a')].edges, vec![Edge { to: VertexId('b'), weight: 1 }]);
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn try_from_edges_rejects_conflicting_weights() {
+        let result = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'b', 5)]);
+
+        assert_eq!(result.err(), Some(GraphError::ConflictingEdge { from: VertexId('a'), to: VertexId('b') }));
+    }
+
+    fn sample_graph() -> Graph {
+        Graph::try_from_edges(&[
+            ('a', 'b', 4), ('a', 'c', 1), ('c', 'b', 2), ('b', 'd', 1),
+            ('c', 'd', 5), ('d', 'e', 3), ('b', 'e', 7)
+        ]).unwrap()
+    }
+
+    #[test]
+    fn shortest_path_with_cost_matches_distances_and_edge_weights() {
+        let graph = sample_graph();
+        let distances = graph.dijkstra(VertexId('a'));
+
+        for goal in ['a', 'b', 'c', 'd', 'e'].map(VertexId) {
+            let (cost, path) = graph.shortest_path_with_cost(VertexId('a'), goal).unwrap();
+            let summed: u32 = path.windows(2).map(|w| graph.edge_weight(w[0], w[1]).unwrap()).sum();
+
+            assert_eq!(cost, distances[&goal]);
+            assert_eq!(cost, summed);
+            assert_eq!(path.first(), Some(&VertexId('a')));
+            assert_eq!(path.last(), Some(&goal));
+        }
+
+        assert_eq!(
+            graph.shortest_path(VertexId('a'), VertexId('e')),
+            Some(vec![VertexId('a'), VertexId('c'), VertexId('b'), VertexId('d'), VertexId('e')])
+        );
+        assert_eq!(graph.shortest_path_with_cost(VertexId('e'), VertexId('a')), None);
+    }
+
+    #[test]
+    fn extend_keeps_last_vertex_with_duplicate_id() {
+        let mut graph = Graph::new();
+        graph.extend(vec![
+            Vertex { id: VertexId('a'), edges: vec![Edge { to: VertexId('b'), weight: 1 }] },
+            Vertex { id: VertexId('b'), edges: Vec::new() },
+            Vertex { id: VertexId('a'), edges: vec![Edge { to: VertexId('b'), weight: 9 }] }
+        ]);
+
+        assert_eq!(graph.vertices.len(), 2);
+        assert_eq!(graph.edge_weight(VertexId('a'), VertexId('b')), Some(9));
+
+        let collected: Graph = ['x', 'y'].iter().map(|&c| Vertex { id: VertexId(c), edges: Vec::new() }).collect();
+        assert_eq!(collected.vertices.len(), 2);
+    }
+
+    fn grid_vertex(width: usize, x: usize, y: usize) -> VertexId {
+        VertexId(char::from_u32('a' as u32 + (y * width + x) as u32).unwrap())
+    }
+
+    fn directed_grid(width: usize, height: usize) -> Graph {
+        let mut graph = Graph::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if x + 1 < width {
+                    graph.add_edge(grid_vertex(width, x, y), grid_vertex(width, x + 1, y), 1);
+                }
+                if y + 1 < height {
+                    graph.add_edge(grid_vertex(width, x, y), grid_vertex(width, x, y + 1), 1);
+                }
+            }
+        }
+
+        graph
+    }
+
+    #[test]
+    fn all_shortest_paths_on_grid_matches_binomial_count() {
+        let graph = directed_grid(4, 3);
+        let start = grid_vertex(4, 0, 0);
+        let goal = grid_vertex(4, 3, 2);
+        let paths = graph.all_shortest_paths(start, goal);
+
+        // C(3 + 2, 2) monotone lattice paths.
+        assert_eq!(paths.len(), 10);
+
+        let unique: HashSet<_> = paths.iter().cloned().collect();
+        assert_eq!(unique.len(), paths.len());
+
+        for path in &paths {
+            assert_eq!(path.len(), 6);
+            assert_eq!(path.first(), Some(&start));
+            assert_eq!(path.last(), Some(&goal));
+        }
+
+        assert!(graph.all_shortest_paths(goal, start).is_empty());
+    }
+
+    #[test]
+    fn all_shortest_paths_keeps_zero_weight_ties() {
+        let graph = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'c', 1), ('c', 'b', 0)]).unwrap();
+        let (a, b, c) = (VertexId('a'), VertexId('b'), VertexId('c'));
+
+        assert_eq!(graph.all_shortest_paths(a, b), vec![vec![a, b], vec![a, c, b]]);
+
+        // A zero-weight cycle would allow endless detours; only simple routes count.
+        let cyclic = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 0), ('c', 'b', 0), ('c', 'd', 1)]).unwrap();
+        let d = VertexId('d');
+        assert_eq!(cyclic.all_shortest_paths(a, d), vec![vec![a, b, c, d]]);
+        assert_eq!(cyclic.all_shortest_paths(a, b), vec![vec![a, b]]);
+
+        // Here the cycle itself carries a second simple route from c to b.
+        let graph = Graph::try_from_edges(&[('c', 'd', 0), ('c', 'b', 0), ('b', 'd', 0), ('d', 'b', 0)]).unwrap();
+        assert_eq!(graph.all_shortest_paths(c, b), vec![vec![c, b], vec![c, d, b]]);
+        assert_eq!(graph.all_shortest_paths(c, d), vec![vec![c, b, d], vec![c, d]]);
+    }
+
+    /// Every simple path from `start` to `goal` whose cost matches the cheapest
+    /// one, sorted; costs use the lightest of any parallel edges.
+    fn brute_force_shortest_paths(graph: &Graph, start: VertexId, goal: VertexId) -> Vec<Vec<VertexId>> {
+        fn walk(graph: &Graph, path: &mut Vec<VertexId>, cost: u32, goal: VertexId, found: &mut Vec<(u32, Vec<VertexId>)>) {
+            let current = *path.last().unwrap();
+
+            if current == goal {
+                found.push((cost, path.clone()));
+                return;
+            }
+
+            let mut next: Vec<VertexId> = graph.vertices[&current].edges.iter().map(|e| e.to).collect();
+            next.sort();
+            next.dedup();
+
+            for to in next {
+                if !path.contains(&to) {
+                    path.push(to);
+                    walk(graph, path, cost + graph.edge_weight(current, to).unwrap(), goal, found);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        walk(graph, &mut vec![start], 0, goal, &mut found);
+
+        let best = found.iter().map(|(cost, _)| *cost).min();
+        let mut paths: Vec<Vec<VertexId>> = found.into_iter().filter(|(cost, _)| Some(*cost) == best).map(|(_, path)| path).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Small random graphs where most edges weigh 0 or 1, so zero-weight
+    /// cycles and ties are common.
+    fn zero_weight_graphs() -> impl Iterator<Item = Graph> {
+        (0..200).map(|seed| {
+            let mut graph = random_graph(seed, 3 + (seed as u32 % 4), 4 + (seed as usize % 9));
+            for edge in graph.vertices.values_mut().flat_map(|v| &mut v.edges) {
+                edge.weight %= 2;
+            }
+            graph
+        })
+    }
+
+    #[test]
+    fn all_shortest_paths_matches_brute_force_with_zero_weights() {
+        for graph in zero_weight_graphs() {
+            for &start in graph.vertices.keys() {
+                for &goal in graph.vertices.keys() {
+                    assert_eq!(graph.all_shortest_paths(start, goal), brute_force_shortest_paths(&graph, start, goal));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn count_shortest_paths_on_grid_matches_binomial_coefficient() {
+        let graph = directed_grid(5, 4);
+        let start = grid_vertex(5, 0, 0);
+        let goal = grid_vertex(5, 4, 3);
+
+        // C(4 + 3, 3) monotone lattice paths.
+        assert_eq!(graph.count_shortest_paths(start, goal), 35);
+        assert_eq!(graph.count_shortest_paths(start, start), 1);
+        assert_eq!(graph.count_shortest_paths(goal, start), 0);
+        assert_eq!(graph.count_shortest_paths(start, goal) as usize, graph.all_shortest_paths(start, goal).len());
+
+        let zero_weight = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'c', 1), ('c', 'b', 0)]).unwrap();
+        assert_eq!(zero_weight.count_shortest_paths(VertexId('a'), VertexId('b')), 2);
+
+        let cyclic = Graph::try_from_edges(&[('c', 'd', 0), ('c', 'b', 0), ('b', 'd', 0), ('d', 'b', 0)]).unwrap();
+        assert_eq!(cyclic.count_shortest_paths(VertexId('c'), VertexId('b')), 2);
+        assert_eq!(cyclic.count_shortest_paths(VertexId('b'), VertexId('c')), 0);
+    }
+
+    #[test]
+    fn count_shortest_paths_matches_brute_force_with_zero_weights() {
+        for graph in zero_weight_graphs() {
+            for &start in graph.vertices.keys() {
+                for &goal in graph.vertices.keys() {
+                    assert_eq!(graph.count_shortest_paths(start, goal) as usize, brute_force_shortest_paths(&graph, start, goal).len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn add_edges_inserts_every_edge_from_large_iterator() {
+        let id = |i: u32| VertexId(char::from_u32(0x4E00 + i).unwrap());
+        let mut graph = Graph::new();
+
+        graph.add_edges((0..5000).flat_map(|i| [(id(i), id(i + 1), i), (id(i), id((i * 7) % 5000), 1)]));
+
+        assert_eq!(graph.edge_count(), 10_000);
+        assert_eq!(graph.vertices.len(), 5001);
+        assert_eq!(graph.edge_weight(id(1234), id(1235)), Some(1234));
+        assert!(graph.validate().is_ok());
+
+        // Dense input: many edges over few vertices must not size the map per edge.
+        let mut dense = Graph::new();
+        dense.add_edges((0..20_000).map(|i| (id(i % 100), id((i / 100) % 100), i)));
+        assert_eq!(dense.edge_count(), 20_000);
+        assert_eq!(dense.vertices.len(), 100);
+        assert!(dense.vertices.capacity() < 1_000);
+        assert!(dense.vertices.values().all(|v| v.edges.len() == 200));
+    }
+
+    fn is_spanning_tree(graph: &Graph, tree: &[(VertexId, VertexId, u32)]) -> bool {
+        let mut tree_graph = Graph::new();
+
+        for &id in graph.vertices.keys() {
+            tree_graph.add_vertex(Vertex { id, edges: Vec::new() });
+        }
+        for &(from, to, weight) in tree {
+            tree_graph.add_edge(from, to, weight);
+            tree_graph.add_edge(to, from, weight);
+        }
+
+        let root = *graph.vertices.keys().next().unwrap();
+        tree.len() + 1 == graph.vertices.len() && tree_graph.dijkstra(root).len() == graph.vertices.len()
+    }
+
+    #[test]
+    fn min_bottleneck_spanning_tree_minimises_heaviest_edge() {
+        // The heavy edges c-d (6) and d-e (8) are the only way to reach d and e,
+        // so every spanning tree has a bottleneck of at least 8.
+        let graph = Graph::try_from_edges(&[
+            ('a', 'b', 1), ('b', 'c', 2), ('a', 'c', 5),
+            ('c', 'd', 6), ('b', 'd', 7), ('d', 'e', 8)
+        ]).unwrap();
+
+        let tree = graph.min_bottleneck_spanning_tree().unwrap();
+        let bottleneck = tree.iter().map(|&(_, _, w)| w).max().unwrap();
+        let total: u32 = tree.iter().map(|&(_, _, w)| w).sum();
+
+        assert!(is_spanning_tree(&graph, &tree));
+        assert_eq!(bottleneck, 8);
+        assert_eq!(total, 17);
+
+        // Swapping in the heavier a-c and b-d edges keeps the same bottleneck but
+        // a larger sum: minimising the maximum does not pin down the tree.
+        let alternative = [
+            (VertexId('a'), VertexId('b'), 1), (VertexId('a'), VertexId('c'), 5),
+            (VertexId('b'), VertexId('d'), 7), (VertexId('d'), VertexId('e'), 8)
+        ];
+        assert!(is_spanning_tree(&graph, &alternative));
+        assert_eq!(alternative.iter().map(|&(_, _, w)| w).max(), Some(bottleneck));
+        assert!(alternative.iter().map(|&(_, _, w)| w).sum::<u32>() > total);
+
+        let mut disconnected = Graph::try_from_edges(&[('a', 'b', 1)]).unwrap();
+        disconnected.add_vertex(Vertex { id: VertexId('z'), edges: Vec::new() });
+        assert_eq!(disconnected.min_bottleneck_spanning_tree(), None);
+    }
+
+    #[test]
+    fn dijkstra_sorted_lists_reachable_vertices_in_id_order() {
+        let mut graph = sample_graph();
+        graph.add_vertex(Vertex { id: VertexId('z'), edges: Vec::new() });
+
+        let sorted = graph.dijkstra_sorted(VertexId('a'));
+
+        assert_eq!(sorted, vec![
+            (VertexId('a'), 0), (VertexId('b'), 3), (VertexId('c'), 1), (VertexId('d'), 4), (VertexId('e'), 7)
+        ]);
+        assert!(sorted.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn k_nearest_stops_after_k_settlements() {
+        let graph = directed_grid(6, 6);
+        let start = grid_vertex(6, 0, 0);
+
+        let mut full: Vec<(VertexId, u32)> = graph.dijkstra_sorted(start).into_iter().filter(|&(v, _)| v != start).collect();
+        full.sort_by_key(|&(v, d)| (d, v));
+
+        for k in [0, 1, 4, 10] {
+            let (nearest, settled) = graph.k_nearest_with_settled(start, k);
+
+            // Start, every vertex up to the k-th distance, and the first one past it.
+            let expected = match k {
+                0 => 1,
+                _ => 2 + full.iter().filter(|&&(_, d)| d <= full[k - 1].1).count(),
+            };
+            assert_eq!(settled, expected);
+            assert!(settled < graph.vertices.len());
+            assert_eq!(nearest, full[..k].to_vec());
+            assert_eq!(graph.k_nearest(start, k), nearest);
+        }
+
+        assert_eq!(graph.k_nearest(start, 1000).len(), full.len());
+
+        // c settles before a, though both sit at distance 1.
+        let zero_weight = Graph::try_from_edges(&[('s', 'c', 1), ('c', 'a', 0), ('s', 'z', 2)]).unwrap();
+        let (s, a, c) = (VertexId('s'), VertexId('a'), VertexId('c'));
+        assert_eq!(zero_weight.k_nearest(s, 1), vec![(a, 1)]);
+        assert_eq!(zero_weight.k_nearest(s, 2), vec![(a, 1), (c, 1)]);
+    }
+
+    #[cfg(feature = "dimacs")]
+    #[test]
+    fn from_dimacs_parses_arcs_and_skips_comments() {
+        let input = "c sample road graph\np sp 3 3\nc arcs follow\na 1 2 4\na 2 3 1\n\na 1 3 7\n";
+        let graph = Graph::from_dimacs(input.as_bytes()).unwrap();
+        let id = |n: u32| VertexId(char::from_u32(n).unwrap());
+
+        assert_eq!(graph.vertices.len(), 3);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph.dijkstra(id(1))[&id(3)], 5);
+    }
+
+    #[cfg(feature = "dimacs")]
+    #[test]
+    fn from_dimacs_rejects_malformed_input() {
+        let cases = [
+            ("a 1 2 3\n", 1),
+            ("p sp 2 1\na 1 x 3\n", 2),
+            ("p sp 2 1\na 1 3 3\n", 2),
+            ("p sp 2 2\na 1 2 3\n", 2),
+            ("p sp 2 1\nq 1 2\n", 2),
+            ("c only a comment\n", 1),
+            ("p sp 4000000000 0\n", 1),
+            ("c header\np sp 55296 0\n", 2)
+        ];
+
+        for (input, expected_line) in cases {
+            match Graph::from_dimacs(input.as_bytes()) {
+                Err(GraphError::Parse { line, .. }) => assert_eq!(line, expected_line, "{:?}", input),
+                other => panic!("expected parse error for {:?}, got {:?}", input, other.map(|g| g.vertices.len())),
+            }
+        }
+    }
+
+    fn undirected_path(ids: &[char], weight: u32) -> Graph {
+        let mut graph = Graph::new();
+
+        for pair in ids.windows(2) {
+            graph.add_edge(VertexId(pair[0]), VertexId(pair[1]), weight);
+            graph.add_edge(VertexId(pair[1]), VertexId(pair[0]), weight);
+        }
+
+        graph
+    }
+
+    #[test]
+    fn approximate_diameter_is_exact_on_path_graph() {
+        let graph = undirected_path(&['c', 'a', 'e', 'b', 'd'], 3);
+
+        assert_eq!(graph.approximate_diameter(), Some(12));
+        assert_eq!(graph.approximate_diameter(), graph.diameter());
+        assert_eq!(Graph::new().approximate_diameter(), None);
+
+        let graph = sample_graph();
+        assert!(graph.approximate_diameter().unwrap() <= graph.diameter().unwrap());
+    }
+
+    #[test]
+    fn cut_edges_cross_between_halves() {
+        let mut graph = Graph::new();
+        graph.add_edges([
+            (VertexId('a'), VertexId('b'), 1), (VertexId('b'), VertexId('a'), 1),
+            (VertexId('c'), VertexId('d'), 2), (VertexId('d'), VertexId('c'), 2),
+            (VertexId('a'), VertexId('c'), 5), (VertexId('d'), VertexId('b'), 7),
+            (VertexId('b'), VertexId('d'), u32::MAX)
+        ]);
+        let left: HashSet<VertexId> = HashSet::from([VertexId('a'), VertexId('b')]);
+
+        assert_eq!(graph.cut_edges(&left), vec![
+            (VertexId('a'), VertexId('c'), 5),
+            (VertexId('b'), VertexId('d'), u32::MAX),
+            (VertexId('d'), VertexId('b'), 7)
+        ]);
+        assert_eq!(graph.cut_weight(&left), 12 + u64::from(u32::MAX));
+        assert!(graph.cut_edges(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn min_cut_matches_max_flow_and_disconnects() {
+        let mut graph = Graph::new();
+        graph.add_edges([
+            (VertexId('s'), VertexId('a'), 10), (VertexId('s'), VertexId('b'), 5),
+            (VertexId('a'), VertexId('b'), 15), (VertexId('a'), VertexId('t'), 4),
+            (VertexId('b'), VertexId('t'), 3), (VertexId('b'), VertexId('t'), 3),
+            (VertexId('b'), VertexId('a'), 2), (VertexId('t'), VertexId('s'), 9)
+        ]);
+        let (s, t) = (VertexId('s'), VertexId('t'));
+
+        assert_eq!(graph.max_flow(s, t), 10);
+
+        let (value, cut) = graph.min_cut(s, t);
+        assert_eq!(value, graph.max_flow(s, t));
+        assert_eq!(cut, vec![(VertexId('a'), VertexId('t')), (VertexId('b'), VertexId('t'))]);
+
+        let mut cut_graph = graph.clone();
+        for &(from, to) in &cut {
+            cut_graph.remove_edge(from, to).unwrap();
+        }
+        assert!(!cut_graph.path_exists(s, t));
+        assert_eq!(cut_graph.max_flow(s, t), 0);
+
+        assert_eq!(graph.max_flow(s, s), 0);
+        assert_eq!(graph.min_cut(t, VertexId('z')), (0, Vec::new()));
+
+        // A dangling edge carries no flow and is not part of the cut.
+        let mut dangling = graph.clone();
+        dangling.vertices.get_mut(&s).unwrap().edges.push(Edge { to: VertexId('z'), weight: 50 });
+        assert_eq!(dangling.min_cut(s, t), (value, cut));
+    }
+
+    #[test]
+    fn shortest_path_iter_matches_shortest_path() {
+        let graph = sample_graph();
+
+        for goal in ['a', 'b', 'c', 'd', 'e'] {
+            let lazy: Vec<VertexId> = graph.shortest_path_iter(VertexId('a'), VertexId(goal)).unwrap().collect();
+            assert_eq!(Some(lazy), graph.shortest_path(VertexId('a'), VertexId(goal)));
+        }
+
+        let tied = Graph::try_from_edges(&[('a', 'x', 1), ('x', 'g', 2), ('a', 'y', 2), ('y', 'g', 1)]).unwrap();
+        let lazy: Vec<VertexId> = tied.shortest_path_iter(VertexId('a'), VertexId('g')).unwrap().collect();
+        assert_eq!(Some(lazy), tied.shortest_path(VertexId('a'), VertexId('g')));
+
+        let lazy: Vec<VertexId> = graph.shortest_path_iter(VertexId('b'), VertexId('b')).unwrap().collect();
+        assert_eq!(lazy, vec![VertexId('b')]);
+
+        assert!(graph.shortest_path_iter(VertexId('e'), VertexId('a')).is_none());
+        assert!(graph.shortest_path_iter(VertexId('a'), VertexId('z')).is_none());
+    }
+
+    #[test]
+    fn dedup_edges_keeps_lightest_parallel_edge() {
+        let mut graph = sample_graph();
+        graph.add_edges([
+            (VertexId('a'), VertexId('b'), 9), (VertexId('a'), VertexId('b'), 2),
+            (VertexId('d'), VertexId('e'), 3), (VertexId('c'), VertexId('d'), 8)
+        ]);
+        let before = graph.edge_count();
+        let expected = graph.dijkstra(VertexId('a'));
+
+        assert_eq!(graph.dedup_edges(), 4);
+        assert_eq!(graph.edge_count(), before - 4);
+        assert_eq!(graph.edge_weight(VertexId('a'), VertexId('b')), Some(2));
+        assert_eq!(graph.edge_weight(VertexId('c'), VertexId('d')), Some(5));
+        assert_eq!(graph.vertices[&VertexId('a')].edges.len(), 2);
+        assert_eq!(graph.dijkstra(VertexId('a')), expected);
+        assert_eq!(graph.dedup_edges(), 0);
+    }
+
+    #[test]
+    fn best_path_generalises_dijkstra_and_widest_path() {
+        fn widest_by_brute_force(graph: &Graph, current: VertexId, goal: VertexId, width: u32, seen: &mut HashSet<VertexId>) -> Option<u32> {
+            if current == goal {
+                return Some(width);
+            }
+
+            let mut best = None;
+            for edge in &graph.vertices[&current].edges {
+                if seen.insert(edge.to) {
+                    let found = widest_by_brute_force(graph, edge.to, goal, width.min(edge.weight), seen);
+                    best = best.max(found);
+                    seen.remove(&edge.to);
+                }
+            }
+            best
+        }
+
+        let graph = sample_graph();
+        let start = VertexId('a');
+
+        for goal in ['a', 'b', 'c', 'd', 'e'] {
+            let goal = VertexId(goal);
+            let sum = graph.best_path(start, goal, 0u32, |&d, w| d + w, |a, b| a < b);
+            assert_eq!(sum, graph.shortest_path_with_cost(start, goal));
+
+            let widest = graph.best_path(start, goal, u32::MAX, |&c, w| c.min(w), |a, b| a > b);
+            let expected = widest_by_brute_force(&graph, start, goal, u32::MAX, &mut HashSet::from([start]));
+            assert_eq!(widest.as_ref().map(|&(width, _)| width), expected);
+
+            let (width, path) = widest.unwrap();
+            let bottleneck = path.windows(2).map(|p| graph.edge_weight(p[0], p[1]).unwrap()).min().unwrap_or(u32::MAX);
+            assert_eq!(bottleneck, width);
+        }
+
+        assert_eq!(graph.best_path(start, VertexId('e'), u32::MAX, |&c, w| c.min(w), |a, b| a > b).unwrap().0, 4);
+        assert!(graph.best_path(VertexId('e'), start, 0u32, |&d, w| d + w, |a, b| a < b).is_none());
+    }
+
+    #[test]
+    fn center_of_path_graph_is_middle_vertex() {
+        let graph = undirected_path(&['a', 'b', 'c', 'd', 'e'], 2);
+
+        assert_eq!(graph.center(), Some(VertexId('c')));
+        assert_eq!(graph.eccentricity(VertexId('c')), Some(4));
+        assert_eq!(graph.eccentricity(VertexId('a')), Some(8));
+        assert_eq!(graph.all_pairs_shortest_paths()[&VertexId('b')][&VertexId('e')], 6);
+
+        // Two middle vertices tie; the smaller id wins.
+        assert_eq!(undirected_path(&['a', 'b', 'c', 'd'], 1).center(), Some(VertexId('b')));
+        assert_eq!(Graph::new().center(), None);
+    }
+
+    #[test]
+    fn would_create_cycle_flags_back_edges_only() {
+        let mut graph = Graph::new();
+        graph.add_edges([
+            (VertexId('a'), VertexId('b'), 1), (VertexId('b'), VertexId('c'), 1), (VertexId('c'), VertexId('d'), 1)
+        ]);
+
+        assert!(graph.would_create_cycle(VertexId('d'), VertexId('a')));
+        assert!(graph.would_create_cycle(VertexId('c'), VertexId('b')));
+        assert!(graph.would_create_cycle(VertexId('b'), VertexId('b')));
+        assert!(!graph.would_create_cycle(VertexId('a'), VertexId('d')));
+        assert!(!graph.would_create_cycle(VertexId('d'), VertexId('z')));
+
+        graph.add_edge(VertexId('a'), VertexId('d'), 1);
+        assert!(graph.is_dag());
+        graph.add_edge(VertexId('d'), VertexId('a'), 1);
+        assert!(!graph.is_dag());
+    }
+
+    #[test]
+    fn is_dag_detects_cycles_and_self_loops() {
+        assert!(sample_graph().is_dag());
+        assert!(directed_grid(3, 3).is_dag());
+        assert!(Graph::new().is_dag());
+
+        let cyclic = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('c', 'a', 1), ('c', 'd', 1)]).unwrap();
+        assert!(!cyclic.is_dag());
+
+        let self_loop = Graph::try_from_edges(&[('a', 'a', 1)]).unwrap();
+        assert!(!self_loop.is_dag());
+    }
+
+    #[test]
+    fn edge_weights_matches_individual_lookups() {
+        let mut graph = sample_graph();
+        graph.add_edge(VertexId('a'), VertexId('b'), 2);
+
+        let ids: Vec<VertexId> = ['a', 'b', 'c', 'd', 'e', 'z'].into_iter().map(VertexId).collect();
+        let pairs: Vec<(VertexId, VertexId)> = ids.iter().flat_map(|&from| ids.iter().map(move |&to| (from, to))).collect();
+
+        for batch in [&pairs[..5], &pairs[..]] {
+            let expected: Vec<Option<u32>> = batch.iter().map(|&(from, to)| graph.edge_weight(from, to)).collect();
+            assert_eq!(graph.edge_weights(batch), expected);
+        }
+
+        assert!(pairs.len() >= EDGE_INDEX_THRESHOLD);
+        assert_eq!(graph.edge_weights(&[(VertexId('a'), VertexId('b'))]), vec![Some(2)]);
+    }
+
+    #[test]
+    fn shortest_path_bottleneck_reports_heaviest_hop() {
+        let graph = sample_graph();
+
+        assert_eq!(
+            graph.shortest_path_edges(VertexId('a'), VertexId('e')),
+            Some(vec![
+                (VertexId('a'), VertexId('c'), 1), (VertexId('c'), VertexId('b'), 2),
+                (VertexId('b'), VertexId('d'), 1), (VertexId('d'), VertexId('e'), 3)
+            ])
+        );
+        assert_eq!(
+            graph.shortest_path_bottleneck(VertexId('a'), VertexId('e')),
+            Some((3, (VertexId('d'), VertexId('e'))))
+        );
+        assert_eq!(graph.shortest_path_bottleneck(VertexId('e'), VertexId('a')), None);
+        assert_eq!(graph.shortest_path_bottleneck(VertexId('a'), VertexId('a')), None);
+    }
+
+    #[test]
+    fn contract_vertex_preserves_remaining_distances() {
+        let original = sample_graph();
+        let mut contracted = sample_graph();
+        assert_eq!(contracted.contract_vertex(VertexId('b')), Ok(()));
+        assert_eq!(contracted.contract_vertex(VertexId('b')), Err(GraphError::VertexNotFound(VertexId('b'))));
+
+        assert!(!contracted.vertices.contains_key(&VertexId('b')));
+        assert!(contracted.validate().is_ok());
+
+        for from in ['a', 'c', 'd', 'e'].map(VertexId) {
+            let mut expected = original.dijkstra(from);
+            expected.remove(&VertexId('b'));
+
+            assert_eq!(contracted.dijkstra(from), expected);
+        }
+
+        // c -> b -> d (3) undercuts the existing c -> d (5) edge.
+        assert_eq!(contracted.edge_weight(VertexId('c'), VertexId('d')), Some(3));
+        assert_eq!(contracted.vertices[&VertexId('c')].edges.iter().filter(|e| e.to == VertexId('d')).count(), 1);
+    }
+
+    #[test]
+    fn distance_rings_match_point_queries() {
+        let graph = sample_graph();
+        let start = VertexId('a');
+
+        assert_eq!(graph.at_distance(start, 0), vec![start]);
+        assert_eq!(graph.at_distance(start, 3), vec![VertexId('b')]);
+        assert!(graph.at_distance(start, 2).is_empty());
+
+        let mut union: Vec<VertexId> = (1..=4).flat_map(|d| graph.at_distance(start, d)).collect();
+        union.sort();
+
+        assert_eq!(graph.between_distances(start, 1, 4), union);
+        assert_eq!(union, vec![VertexId('b'), VertexId('c'), VertexId('d')]);
+    }
+
+    #[test]
+    fn ida_star_matches_astar_costs() {
+        let grid = directed_grid(5, 5);
+        let manhattan = |goal: (usize, usize)| {
+            move |v: VertexId| {
+                let index = v.0 as usize - 'a' as usize;
+                (goal.0.abs_diff(index % 5) + goal.1.abs_diff(index / 5)) as u32
+            }
+        };
+
+        let start = grid_vertex(5, 0, 0);
+        for (x, y) in [(4, 4), (2, 3), (0, 0)] {
+            let goal = grid_vertex(5, x, y);
+            let astar = grid.astar(start, goal, manhattan((x, y))).unwrap();
+            let ida = grid.ida_star(start, goal, manhattan((x, y))).unwrap();
+
+            assert_eq!(ida.0, astar.0);
+            assert_eq!(ida.0, (x + y) as u32);
+            assert_eq!(ida.1.len(), x + y + 1);
+        }
+
+        let graph = sample_graph();
+        for goal in ['a', 'b', 'c', 'd', 'e'].map(VertexId) {
+            let astar = graph.astar(VertexId('a'), goal, |_| 0);
+            let ida = graph.ida_star(VertexId('a'), goal, |_| 0);
+
+            assert_eq!(astar.as_ref().map(|r| r.0), Some(graph.dijkstra(VertexId('a'))[&goal]));
+            assert_eq!(ida.map(|r| r.0), astar.map(|r| r.0));
+        }
+
+        assert_eq!(graph.astar(VertexId('e'), VertexId('a'), |_| 0), None);
+        assert_eq!(graph.ida_star(VertexId('e'), VertexId('a'), |_| 0), None);
+    }
+
+    #[test]
+    fn verify_shortest_path_checks_edges_and_optimality() {
+        let graph = sample_graph();
+        let path = |ids: &str| ids.chars().map(VertexId).collect::<Vec<_>>();
+
+        assert_eq!(graph.verify_shortest_path(&path("acbde")), Ok(7));
+        assert_eq!(graph.verify_shortest_path(&path("a")), Ok(0));
+        assert_eq!(
+            graph.verify_shortest_path(&path("aed")),
+            Err(PathError::MissingEdge { from: VertexId('a'), to: VertexId('e') })
+        );
+        assert_eq!(
+            graph.verify_shortest_path(&path("abe")),
+            Err(PathError::Suboptimal { claimed: 11, optimal: 7 })
+        );
+        assert_eq!(graph.verify_shortest_path(&[]), Err(PathError::Empty));
+        assert_eq!(graph.verify_shortest_path(&path("z")), Err(PathError::VertexNotFound(VertexId('z'))));
+
+        let mut dangling = sample_graph();
+        dangling.vertices.get_mut(&VertexId('a')).unwrap().edges.push(Edge { to: VertexId('z'), weight: 1 });
+        assert_eq!(dangling.verify_shortest_path(&path("az")), Err(PathError::VertexNotFound(VertexId('z'))));
+        assert_eq!(dangling.verify_shortest_path(&path("azb")), Err(PathError::VertexNotFound(VertexId('z'))));
+    }
+
+    #[test]
+    fn shortest_path_avoiding_detours_around_blocked_vertices() {
+        let graph = sample_graph();
+        let blocked = |ids: &str| ids.chars().map(VertexId).collect::<HashSet<_>>();
+
+        assert_eq!(
+            graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("")),
+            Ok(graph.shortest_path_with_cost(VertexId('a'), VertexId('e')))
+        );
+        assert_eq!(
+            graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("c")),
+            Ok(Some((8, vec![VertexId('a'), VertexId('b'), VertexId('d'), VertexId('e')])))
+        );
+        assert_eq!(
+            graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("b")),
+            Ok(Some((9, vec![VertexId('a'), VertexId('c'), VertexId('d'), VertexId('e')])))
+        );
+        assert_eq!(graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("bd")), Ok(None));
+        assert_eq!(
+            graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("e")),
+            Err(GraphError::BlockedEndpoint(VertexId('e')))
+        );
+        assert_eq!(
+            graph.shortest_path_avoiding(VertexId('a'), VertexId('e'), &blocked("ae")),
+            Err(GraphError::BlockedEndpoint(VertexId('a')))
+        );
+    }
+
+    #[test]
+    fn turn_penalty_reroutes_path() {
+        let graph = sample_graph();
+        let free = graph.shortest_path_with_turn_cost(VertexId('a'), VertexId('e'), |_, _, _| 0);
+
+        assert_eq!(free, graph.shortest_path_with_cost(VertexId('a'), VertexId('e')));
+
+        // Penalise turning from c onto b at the b junction.
+        let penalised = graph.shortest_path_with_turn_cost(VertexId('a'), VertexId('e'), |prev, current, _| {
+            if prev == VertexId('c') && current == VertexId('b') { 100 } else { 0 }
+        });
+
+        assert_eq!(penalised, Some((8, vec![VertexId('a'), VertexId('b'), VertexId('d'), VertexId('e')])));
+
+        // A vertex may be revisited when that avoids a penalty.
+        let square = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('b', 'd', 1), ('d', 'b', 1)]).unwrap();
+        let rerouted = square.shortest_path_with_turn_cost(VertexId('a'), VertexId('c'), |prev, _, next| {
+            if prev == VertexId('a') && next == VertexId('c') { 50 } else { 0 }
+        });
+
+        assert_eq!(rerouted, Some((4, vec![VertexId('a'), VertexId('b'), VertexId('d'), VertexId('b'), VertexId('c')])));
+    }
+
+    #[test]
+    fn shrink_to_fit_reduces_estimated_memory() {
+        let id = |i: u32| VertexId(char::from_u32(0x4E00 + i).unwrap());
+        let mut graph = Graph::new();
+        graph.add_edges((0..1000).map(|i| (id(i), id(i + 1), 1)));
+
+        for i in 10..1001 {
+            graph.remove_vertex(id(i));
+        }
+
+        let before = graph.estimated_memory_bytes();
+        graph.shrink_to_fit();
+
+        assert!(graph.estimated_memory_bytes() < before);
+        assert_eq!(graph.vertices.len(), 10);
+        assert_eq!(graph.dijkstra(id(0))[&id(9)], 9);
+    }
+
+    #[test]
+    fn component_subgraphs_partition_vertices_without_crossing_edges() {
+        let mut graph = sample_graph();
+        graph.add_edges([
+            (VertexId('x'), VertexId('y'), 1), (VertexId('z'), VertexId('y'), 2), (VertexId('p'), VertexId('p'), 3)
+        ]);
+
+        let components = graph.component_subgraphs();
+        let total: usize = components.iter().map(|c| c.vertices.len()).sum();
+
+        assert_eq!(components.len(), 3);
+        assert_eq!(total, graph.vertices.len());
+        assert_eq!(components.iter().map(|c| c.edge_count()).sum::<usize>(), graph.edge_count());
+
+        for component in &components {
+            assert!(component.validate().is_ok());
+        }
+
+        assert_eq!(graph.weakly_connected_components()[2], vec![VertexId('x'), VertexId('y'), VertexId('z')]);
+    }
+
+    #[test]
+    fn vertex_index_and_vertex_by_index_are_inverses() {
+        let graph = sample_graph();
+
+        for id in graph.vertices.keys() {
+            assert_eq!(graph.vertex_by_index(graph.vertex_index(*id).unwrap()), Some(*id));
+        }
+        for index in 0..graph.vertices.len() {
+            assert_eq!(graph.vertex_index(graph.vertex_by_index(index).unwrap()), Some(index));
+        }
+
+        assert_eq!(graph.vertex_index(VertexId('c')), Some(2));
+        assert_eq!(graph.vertex_index(VertexId('z')), None);
+        assert_eq!(graph.vertex_by_index(5), None);
+    }
+
+    #[test]
+    fn straight_line_heuristic_is_zero_at_goal() {
+        let (graph, coordinates) = Graph::grid(4, 4, 1);
+
+        let mut spatial = SpatialGraph::new(graph);
+        for (id, (x, y)) in coordinates {
+            spatial.set_coord(id, (x as f64, y as f64));
+        }
+
+        let goal = grid_vertex(4, 3, 1);
+        let heuristic = spatial.straight_line_heuristic(goal);
+
+        assert_eq!(heuristic(goal), 0);
+        assert_eq!(heuristic(grid_vertex(4, 0, 1)), 3);
+        assert_eq!(heuristic(grid_vertex(4, 0, 3)), 3);
+        assert_eq!(heuristic(VertexId('z')), 0);
+        assert_eq!(spatial.get_coord(goal), Some((3.0, 1.0)));
+
+        let start = grid_vertex(4, 0, 3);
+        assert_eq!(spatial.astar(start, goal).map(|r| r.0), Some(spatial.graph.dijkstra(start)[&goal]));
+    }
+
+    fn random_graph(seed: u64, vertex_count: u32, edge_count: usize) -> Graph {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407) | 1;
+        let mut next = move |bound: u32| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as u32
+        };
+
+        let id = |i: u32| VertexId(char::from_u32('a' as u32 + i).unwrap());
+        let mut graph: Graph = (0..vertex_count).map(|i| Vertex { id: id(i), edges: Vec::new() }).collect();
+
+        for _ in 0..edge_count {
+            let (from, to, weight) = (next(vertex_count), next(vertex_count), next(20));
+            graph.add_edge(id(from), id(to), weight);
+        }
+
+        graph
+    }
+
+    #[test]
+    fn matrix_round_trip_preserves_vertices_and_min_edges() {
+        for seed in 0..50 {
+            let graph = random_graph(seed, 1 + (seed as u32 % 12), (seed as usize * 3) % 40);
+            let matrix = MatrixGraph::from(&graph);
+            let round_trip = Graph::from(&matrix);
+
+            let mut expected = graph.clone();
+            for vertex in expected.vertices.values_mut() {
+                let mut edges: Vec<Edge> = Vec::new();
+                let mut sorted = vertex.edges.clone();
+                sorted.sort_by_key(|e| (e.to, e.weight));
+                for edge in sorted {
+                    if edges.last().map(|e| e.to) != Some(edge.to) {
+                        edges.push(edge);
+                    }
+                }
+                vertex.edges = edges;
+            }
+
+            assert_eq!(round_trip, expected, "seed {}", seed);
+            assert_eq!(MatrixGraph::from(&round_trip), matrix);
+
+            for vertex in graph.vertices.values() {
+                for edge in &vertex.edges {
+                    assert_eq!(matrix.weight(vertex.id, edge.to), graph.edge_weight(vertex.id, edge.to));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_shortest_path_costs_manhattan_distance() {
+        let (graph, coordinates) = Graph::grid(6, 4, 3);
+
+        assert_eq!(graph.vertices.len(), 24);
+        assert_eq!(graph.edge_count(), 2 * (5 * 4 + 6 * 3));
+        assert_eq!(coordinates[&VertexId('a')], (0, 0));
+
+        let corner = |x: usize, y: usize| *coordinates.iter().find(|(_, &c)| c == (x, y)).unwrap().0;
+        let (start, goal) = (corner(0, 0), corner(5, 3));
+
+        assert_eq!(graph.dijkstra(start)[&goal], 3 * (5 + 3));
+
+        let (gx, gy) = coordinates[&goal];
+        let manhattan = |v: VertexId| {
+            let (x, y) = coordinates[&v];
+            (3 * (x.abs_diff(gx) + y.abs_diff(gy))) as u32
+        };
+        assert_eq!(graph.astar(start, goal, manhattan).map(|r| r.0), Some(24));
+    }
+
+    #[test]
+    fn min_mean_cycle_picks_smaller_average() {
+        let graph = Graph::try_from_edges(&[
+            ('a', 'b', 4), ('b', 'a', 6),
+            ('a', 'c', 100),
+            ('c', 'd', 1), ('d', 'e', 2), ('e', 'c', 3)
+        ]).unwrap();
+
+        let (mean, cycle) = graph.min_mean_cycle().unwrap();
+
+        assert!((mean - 2.0).abs() < 1e-9);
+        assert_eq!(cycle, vec![VertexId('c'), VertexId('d'), VertexId('e')]);
+
+        let fractional = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'a', 2), ('c', 'c', 2), ('b', 'c', 0)]).unwrap();
+        let (mean, cycle) = fractional.min_mean_cycle().unwrap();
+
+        assert!((mean - 1.5).abs() < 1e-9);
+        assert_eq!(cycle, vec![VertexId('a'), VertexId('b')]);
+
+        assert_eq!(sample_graph().min_mean_cycle(), None);
+    }
+
+    #[test]
+    fn vertex_id_parses_single_characters_only() {
+        assert_eq!("a".parse::<VertexId>(), Ok(VertexId('a')));
+        assert_eq!("é".parse::<VertexId>(), Ok(VertexId('é')));
+        assert!("".parse::<VertexId>().is_err());
+        assert!("ab".parse::<VertexId>().is_err());
+    }
+
+    #[test]
+    fn degree_distribution_of_directed_star() {
+        let mut graph = Graph::new();
+        graph.add_edges(['b', 'c', 'd', 'e', 'f'].map(|leaf| (VertexId('a'), VertexId(leaf), 1)));
+
+        assert_eq!(graph.degree_distribution(), BTreeMap::from([(0, 5), (5, 1)]));
+
+        graph.add_edges(['b', 'c', 'd', 'e', 'f'].map(|leaf| (VertexId(leaf), VertexId('a'), 1)));
+        assert_eq!(graph.degree_distribution(), BTreeMap::from([(1, 5), (5, 1)]));
+    }
+
+    #[test]
+    fn path_exists_exits_early() {
+        let graph = sample_graph();
+
+        assert!(graph.path_exists(VertexId('a'), VertexId('e')));
+        assert!(graph.path_exists(VertexId('d'), VertexId('d')));
+        assert!(!graph.path_exists(VertexId('e'), VertexId('a')));
+        assert!(!graph.path_exists(VertexId('z'), VertexId('a')));
+
+        let id = |i: u32| VertexId(char::from_u32(0x4E00 + i).unwrap());
+        let mut chain = Graph::new();
+        chain.add_edges((0..5000).map(|i| (id(i), id(i + 1), 1)));
+
+        assert_eq!(chain.path_exists_with_visited(id(0), id(3)), (true, 4));
+        assert_eq!(chain.path_exists_with_visited(id(10), id(0)), (false, 4991));
+    }
+
+    #[test]
+    fn spt_as_tree_covers_reachable_vertices_consistently() {
+        fn walk(graph: &Graph, node: &SptNode, seen: &mut Vec<VertexId>) {
+            seen.push(node.id);
+
+            assert!(node.children.windows(2).all(|w| w[0].id < w[1].id));
+            for child in &node.children {
+                assert_eq!(child.distance, node.distance + graph.edge_weight(node.id, child.id).unwrap());
+                walk(graph, child, seen);
+            }
+        }
+
+        let mut graph = sample_graph();
+        graph.add_vertex(Vertex { id: VertexId('z'), edges: Vec::new() });
+
+        let tree = graph.spt_as_tree(VertexId('a')).unwrap();
+        let mut seen = Vec::new();
+        walk(&graph, &tree, &mut seen);
+        seen.sort();
+
+        let mut reachable: Vec<VertexId> = graph.dijkstra(VertexId('a')).into_keys().collect();
+        reachable.sort();
+
+        assert_eq!(tree.distance, 0);
+        assert_eq!(seen, reachable);
+
+        assert_eq!(graph.spt_as_tree(VertexId('z')), Some(SptNode { id: VertexId('z'), distance: 0, children: Vec::new() }));
+        assert_eq!(graph.spt_as_tree(VertexId('y')), None);
+    }
+
+    #[test]
+    fn dijkstra_by_supports_duration_weights() {
+        use std::time::Duration;
+
+        let durations: HashMap<(VertexId, VertexId), Duration> = HashMap::from([
+            ((VertexId('a'), VertexId('b')), Duration::from_secs(90)),
+            ((VertexId('a'), VertexId('c')), Duration::from_millis(30_500)),
+            ((VertexId('c'), VertexId('b')), Duration::from_secs(45)),
+            ((VertexId('b'), VertexId('d')), Duration::from_secs(600))
+        ]);
+
+        let mut schedule = Graph::new();
+        for &(from, to) in durations.keys() {
+            schedule.add_edge(from, to, 0);
+        }
+
+        let times = schedule.dijkstra_by(VertexId('a'), |from, edge| durations[&(from, edge.to)]);
+
+        assert_eq!(times[&VertexId('a')], Duration::ZERO);
+        assert_eq!(times[&VertexId('b')], Duration::from_millis(75_500));
+        assert_eq!(times[&VertexId('d')], Duration::from_millis(675_500));
+
+        let plain = sample_graph();
+        assert_eq!(plain.dijkstra_by(VertexId('a'), |_, e| e.weight), plain.dijkstra(VertexId('a')));
+    }
+
+    #[test]
+    fn spt_graph_reproduces_distances() {
+        let mut graph = sample_graph();
+        graph.add_edge(VertexId('z'), VertexId('a'), 1);
+
+        let distances = graph.dijkstra(VertexId('a'));
+        let tree = graph.spt_graph(VertexId('a'));
+
+        assert!(tree.is_dag());
+        assert_eq!(tree.vertices.len(), distances.len());
+        assert_eq!(tree.edge_count(), distances.len() - 1);
+        assert_eq!(tree.dijkstra(VertexId('a')), distances);
+    }
+
+    #[test]
+    fn dijkstra_max_hops_only_improves_with_more_hops() {
+        let graph = sample_graph();
+        let start = VertexId('a');
+
+        assert_eq!(graph.dijkstra_max_hops(start, 0), HashMap::from([(start, 0)]));
+        assert_eq!(graph.dijkstra_max_hops(start, 1)[&VertexId('b')], 4);
+        assert_eq!(graph.dijkstra_max_hops(start, 2)[&VertexId('b')], 3);
+        assert_eq!(graph.dijkstra_max_hops(start, 2)[&VertexId('e')], 11);
+        assert_eq!(graph.dijkstra_max_hops(start, 10), graph.dijkstra(start));
+
+        let mut previous = graph.dijkstra_max_hops(start, 0);
+        for hops in 1..6 {
+            let current = graph.dijkstra_max_hops(start, hops);
+
+            for (vertex, distance) in &previous {
+                assert!(current[vertex] <= *distance);
+            }
+
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn iterating_graph_reference_yields_sorted_vertices() {
+        let graph = sample_graph();
+        let mut ids = Vec::new();
+
+        for vertex in &graph {
+            ids.push(vertex.id);
+        }
+
+        assert_eq!(ids, ['a', 'b', 'c', 'd', 'e'].map(VertexId).to_vec());
+        assert_eq!((&graph).into_iter().map(|v| v.edges.len()).sum::<usize>(), graph.edge_count());
+    }
+
+    #[test]
+    fn mutually_reachable_requires_paths_both_ways() {
+        let graph = sample_graph();
+        assert!(!graph.mutually_reachable(VertexId('a'), VertexId('e')));
+
+        let cycle = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('c', 'a', 1), ('c', 'd', 1)]).unwrap();
+        assert!(cycle.mutually_reachable(VertexId('a'), VertexId('c')));
+        assert!(!cycle.mutually_reachable(VertexId('a'), VertexId('d')));
+    }
+
+    #[test]
+    fn is_isomorphic_ignores_vertex_names() {
+        let graph = sample_graph();
+        let renamed = Graph::try_from_edges(&[
+            ('q', 'p', 4), ('q', 'r', 1), ('r', 'p', 2), ('p', 's', 1),
+            ('r', 's', 5), ('s', 't', 3), ('p', 't', 7)
+        ]).unwrap();
+
+        assert!(graph.is_isomorphic(&renamed));
+        assert!(renamed.is_isomorphic(&graph));
+        assert!(Graph::new().is_isomorphic(&Graph::new()));
+
+        // Same shape, one weight changed.
+        let reweighted = Graph::try_from_edges(&[
+            ('q', 'p', 4), ('q', 'r', 1), ('r', 'p', 2), ('p', 's', 1),
+            ('r', 's', 5), ('s', 't', 3), ('p', 't', 8)
+        ]).unwrap();
+        assert!(!graph.is_isomorphic(&reweighted));
+
+        // Same degree sequence, different structure.
+        let cycle = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('c', 'a', 1), ('d', 'e', 1), ('e', 'f', 1), ('f', 'd', 1)]).unwrap();
+        let hexagon = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('c', 'd', 1), ('d', 'e', 1), ('e', 'f', 1), ('f', 'a', 1)]).unwrap();
+        assert!(!cycle.is_isomorphic(&hexagon));
+
+        // Larger graphs are searched too; a transposed grid is the same graph.
+        let grid = directed_grid(5, 4);
+        assert!(grid.vertices.len() > 16);
+        assert!(grid.is_isomorphic(&directed_grid(5, 4)));
+        assert!(grid.is_isomorphic(&directed_grid(4, 5)));
+        assert!(!grid.is_isomorphic(&directed_grid(10, 2)));
+    }
+
+    #[test]
+    fn weight_histogram_buckets_by_lower_bound() {
+        let graph = sample_graph();
+
+        assert_eq!(graph.weight_histogram(2), BTreeMap::from([(0, 2), (2, 2), (4, 2), (6, 1)]));
+        assert_eq!(graph.weight_histogram(5), BTreeMap::from([(0, 5), (5, 2)]));
+        assert_eq!(graph.weight_histogram(0), BTreeMap::from([(0, 7)]));
+        assert!(Graph::new().weight_histogram(3).is_empty());
+    }
+
+    #[test]
+    fn min_outgoing_and_incoming_weights() {
+        let graph = sample_graph();
+
+        assert_eq!(graph.min_outgoing(), HashMap::from([
+            (VertexId('a'), 1), (VertexId('b'), 1), (VertexId('c'), 2), (VertexId('d'), 3)
+        ]));
+        assert_eq!(graph.min_incoming(), HashMap::from([
+            (VertexId('b'), 2), (VertexId('c'), 1), (VertexId('d'), 1), (VertexId('e'), 3)
+        ]));
+    }
+
+    #[test]
+    fn best_meeting_point_minimises_summed_distance() {
+        let graph = Graph::try_from_edges(&[
+            ('a', 'm', 3), ('b', 'm', 2), ('a', 'x', 1), ('x', 'y', 10), ('b', 'y', 1), ('m', 'y', 1), ('z', 'w', 1)
+        ]).unwrap();
+
+        assert_eq!(graph.best_meeting_point(VertexId('a'), VertexId('b')), Some((VertexId('m'), 5)));
+        assert_eq!(graph.best_meeting_point(VertexId('a'), VertexId('a')), Some((VertexId('a'), 0)));
+        assert_eq!(graph.best_meeting_point(VertexId('x'), VertexId('m')), Some((VertexId('y'), 11)));
+        assert_eq!(graph.best_meeting_point(VertexId('z'), VertexId('a')), None);
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn binary_round_trip_preserves_graph() {
+        for seed in 0..10 {
+            let graph = random_graph(seed, 3 + seed as u32, 25);
+            let mut bytes = Vec::new();
+            graph.write_binary(&mut bytes).unwrap();
+
+            assert_eq!(bytes[0], BINARY_FORMAT_VERSION);
+            assert_eq!(bytes.len(), 1 + 4 + 8 * graph.vertices.len() + 8 * graph.edge_count());
+            assert_eq!(Graph::read_binary(bytes.as_slice()).unwrap(), graph);
+        }
+
+        let mut bytes = Vec::new();
+        sample_graph().write_binary(&mut bytes).unwrap();
+
+        bytes[0] = BINARY_FORMAT_VERSION + 1;
+        assert_eq!(Graph::read_binary(bytes.as_slice()).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        bytes[0] = BINARY_FORMAT_VERSION;
+        bytes.truncate(bytes.len() - 2);
+        assert_eq!(Graph::read_binary(bytes.as_slice()).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn read_binary_rejects_hostile_headers() {
+        let huge_vertex_count = [BINARY_FORMAT_VERSION, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(Graph::read_binary(huge_vertex_count.as_slice()).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        let mut huge_edge_count = vec![BINARY_FORMAT_VERSION];
+        huge_edge_count.extend(1u32.to_le_bytes());
+        huge_edge_count.extend(('a' as u32).to_le_bytes());
+        huge_edge_count.extend(u32::MAX.to_le_bytes());
+        assert_eq!(Graph::read_binary(huge_edge_count.as_slice()).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let mut truncated_vertices = vec![BINARY_FORMAT_VERSION];
+        truncated_vertices.extend(1_000_000u32.to_le_bytes());
+        assert_eq!(Graph::read_binary(truncated_vertices.as_slice()).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+
+        assert_eq!(Graph::read_binary([BINARY_FORMAT_VERSION, 0].as_slice()).unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn transitive_closure_agrees_with_path_exists() {
+        let chain = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'c', 1), ('c', 'd', 1)]).unwrap();
+        let closure = chain.transitive_closure();
+        let ids = ['a', 'b', 'c', 'd'].map(VertexId);
+
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(closure[id], ids[i..].iter().copied().collect::<HashSet<_>>());
+        }
+
+        let graph = sample_graph();
+        let closure = graph.transitive_closure();
+        for &a in graph.vertices.keys() {
+            for &b in graph.vertices.keys() {
+                assert_eq!(closure[&a].contains(&b), graph.path_exists(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn dijkstra_dense_covers_every_vertex() {
+        let mut graph = sample_graph();
+        graph.add_vertex(Vertex { id: VertexId('z'), edges: Vec::new() });
+
+        let dense = graph.dijkstra_dense(VertexId('b'));
+
+        assert_eq!(dense.keys().collect::<HashSet<_>>(), graph.vertices.keys().collect::<HashSet<_>>());
+        assert_eq!(dense[&VertexId('e')], Some(4));
+        assert_eq!(dense[&VertexId('a')], None);
+        assert_eq!(dense[&VertexId('z')], None);
+    }
+
+    #[test]
+    fn most_critical_edge_finds_single_point_of_failure() {
+        let graph = sample_graph();
+
+        // Without d -> e the only way to e is b -> e.
+        assert_eq!(
+            graph.most_critical_edge(VertexId('a'), VertexId('e')),
+            Some(((VertexId('d'), VertexId('e')), 10))
+        );
+
+        let mut bridged = sample_graph();
+        bridged.add_edge(VertexId('e'), VertexId('f'), 1);
+        assert_eq!(
+            bridged.most_critical_edge(VertexId('a'), VertexId('f')),
+            Some(((VertexId('e'), VertexId('f')), u32::MAX))
+        );
+
+        assert_eq!(graph.most_critical_edge(VertexId('e'), VertexId('a')), None);
+    }
+
+    #[test]
+    fn count_simple_paths_in_complete_graph() {
+        let mut k4 = Graph::new();
+        for from in ['a', 'b', 'c', 'd'] {
+            for to in ['a', 'b', 'c', 'd'] {
+                if from != to {
+                    k4.add_edge(VertexId(from), VertexId(to), 1);
+                }
+            }
+        }
+
+        // Direct, via one of two vertices, or via both in either order.
+        assert_eq!(k4.count_simple_paths(VertexId('a'), VertexId('d')), 1 + 2 + 2);
+        assert_eq!(k4.count_simple_paths(VertexId('a'), VertexId('a')), 1);
+
+        k4.add_edge(VertexId('a'), VertexId('d'), 5);
+        assert_eq!(k4.count_simple_paths(VertexId('a'), VertexId('d')), 5);
+        assert_eq!(sample_graph().count_simple_paths(VertexId('e'), VertexId('a')), 0);
+    }
+
+    #[test]
+    fn edges_by_weight_sorts_every_edge() {
+        let graph = sample_graph();
+        let edges = graph.edges_by_weight();
+
+        assert_eq!(edges.len(), graph.edge_count());
+        assert!(edges.windows(2).all(|w| (w[0].2, w[0].0, w[0].1) <= (w[1].2, w[1].0, w[1].1)));
+        assert_eq!(&edges[..2], &[(VertexId('a'), VertexId('c'), 1), (VertexId('b'), VertexId('d'), 1)]);
+
+        for vertex in &graph {
+            for edge in &vertex.edges {
+                assert_eq!(edges.iter().filter(|&&e| e == (vertex.id, edge.to, edge.weight)).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn kruskal_matches_prim_and_spans_forests() {
+        let total = |tree: &[(VertexId, VertexId, u32)]| tree.iter().map(|&(_, _, w)| w).sum::<u32>();
+
+        for seed in 0..20 {
+            let graph = random_graph(seed, 8, 30);
+            let kruskal = graph.kruskal_mst();
+            let components = graph.weakly_connected_components().len();
+
+            assert_eq!(kruskal.len(), graph.vertices.len() - components);
+
+            match graph.prim_mst() {
+                Some(prim) => {
+                    assert_eq!(components, 1);
+                    assert_eq!(total(&kruskal), total(&prim));
+                    assert!(is_spanning_tree(&graph, &kruskal));
+                }
+                None => assert!(components > 1),
+            }
+        }
+
+        let mut forest = sample_graph();
+        forest.add_edges([(VertexId('x'), VertexId('y'), 4), (VertexId('y'), VertexId('z'), 2)]);
+        assert_eq!(forest.kruskal_mst().len(), 5 - 1 + 3 - 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "negative edge weight")]
+    fn dijkstra_by_asserts_on_negative_relaxation() {
+        let graph = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'c', 5), ('c', 'b', 0)]).unwrap();
+        let signed: HashMap<(VertexId, VertexId), i32> = HashMap::from([
+            ((VertexId('a'), VertexId('b')), 1),
+            ((VertexId('a'), VertexId('c')), 5),
+            ((VertexId('c'), VertexId('b')), -10)
+        ]);
+
+        graph.dijkstra_by(VertexId('a'), |from, edge| signed[&(from, edge.to)]);
+    }
+
+    #[test]
+    fn periphery_and_radius_of_path_graph() {
+        let graph = undirected_path(&['a', 'b', 'c', 'd', 'e'], 2);
+
+        assert_eq!(graph.periphery(), vec![VertexId('a'), VertexId('e')]);
+        assert_eq!(graph.graph_radius(), Some(4));
+        assert_eq!(graph.eccentricity(graph.center().unwrap()), graph.graph_radius());
+        assert_eq!(graph.diameter(), Some(8));
+
+        assert!(Graph::new().periphery().is_empty());
+        assert_eq!(Graph::new().graph_radius(), None);
+    }
+
+    #[test]
+    fn dijkstra_levels_form_manhattan_rings_on_grid() {
+        let (graph, coordinates) = Graph::grid(4, 3, 1);
+        let start = VertexId('a');
+        let levels = graph.dijkstra_levels(start);
+
+        assert_eq!(levels.len(), 6);
+        assert_eq!(levels[0], (0, vec![start]));
+
+        for (distance, vertices) in &levels {
+            assert!(vertices.windows(2).all(|w| w[0] < w[1]));
+            for vertex in vertices {
+                let (x, y) = coordinates[vertex];
+                assert_eq!((x + y) as u32, *distance);
+            }
+        }
+
+        assert_eq!(levels.iter().map(|(_, v)| v.len()).collect::<Vec<_>>(), vec![1, 2, 3, 3, 2, 1]);
+    }
+
+    #[test]
+    fn query_constraints_match_standalone_methods() {
+        let graph = sample_graph();
+        let (start, goal) = (VertexId('a'), VertexId('e'));
+        let blocked: HashSet<VertexId> = HashSet::from([VertexId('c')]);
+
+        assert_eq!(graph.query(start).run().distances, graph.dijkstra(start));
+        assert_eq!(graph.query(start).to(goal).run().path(), graph.shortest_path_with_cost(start, goal));
+
+        for blocked in [HashSet::new(), blocked.clone(), HashSet::from([VertexId('b'), VertexId('d')])] {
+            assert_eq!(
+                graph.query(start).to(goal).avoiding(&blocked).run().path(),
+                graph.shortest_path_avoiding(start, goal, &blocked).unwrap()
+            );
+        }
+
+        for hops in 0..5 {
+            assert_eq!(graph.query(start).max_hops(hops).run().distances, graph.dijkstra_max_hops(start, hops));
+        }
+
+        for limit in [0, 3, 4, 100] {
+            let mut expected = graph.dijkstra(start);
+            expected.retain(|_, d| *d <= limit);
+            assert_eq!(graph.query(start).within(limit).run().distances, expected);
+        }
+
+        // All constraints together against a filtered subgraph.
+        let mut filtered = sample_graph();
+        filtered.remove_vertex(VertexId('c'));
+        let mut expected = filtered.dijkstra_max_hops(start, 2);
+        expected.retain(|_, d| *d <= 10);
+
+        let result = graph.query(start).avoiding(&blocked).within(10).max_hops(2).run();
+        assert_eq!(result.distances, expected);
+        assert_eq!(result.path_to(VertexId('d')), Some((5, vec![start, VertexId('b'), VertexId('d')])));
+        assert_eq!(graph.query(start).to(goal).avoiding(&blocked).within(10).max_hops(2).run().path(), None);
+        assert_eq!(
+            graph.query(start).to(goal).avoiding(&blocked).within(10).max_hops(3).run().path(),
+            Some((8, vec![start, VertexId('b'), VertexId('d'), goal]))
+        );
+    }
+
+    #[test]
+    fn edge_betweenness_peaks_on_barbell_bridge() {
+        let mut barbell = Graph::new();
+        for (from, to) in [('a', 'b'), ('b', 'c'), ('a', 'c'), ('c', 'd'), ('d', 'e'), ('e', 'f'), ('d', 'f')] {
+            barbell.add_edge(VertexId(from), VertexId(to), 1);
+            barbell.add_edge(VertexId(to), VertexId(from), 1);
+        }
+
+        let betweenness = barbell.edge_betweenness();
+        let bridge = betweenness[&(VertexId('c'), VertexId('d'))];
+
+        // Every one of the 3 x 3 pairs across the bridge uses it in this direction.
+        assert!((bridge - 9.0).abs() < 1e-9);
+        assert_eq!(betweenness.len(), barbell.edge_count());
+        for (&edge, &value) in &betweenness {
+            if edge != (VertexId('c'), VertexId('d')) && edge != (VertexId('d'), VertexId('c')) {
+                assert!(value < bridge);
+            }
+        }
+
+        // Two equal routes split the a -> d traffic evenly.
+        let diamond = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'c', 1), ('b', 'd', 1), ('c', 'd', 1)]).unwrap();
+        let split = diamond.edge_betweenness();
+        assert!((split[&(VertexId('b'), VertexId('d'))] - 1.5).abs() < 1e-9);
+        assert!((split[&(VertexId('a'), VertexId('b'))] - 1.5).abs() < 1e-9);
+
+        // A zero-weight detour ties the direct edge, so a -> b traffic splits.
+        let zero_weight = Graph::try_from_edges(&[('a', 'b', 1), ('a', 'c', 1), ('c', 'b', 0)]).unwrap();
+        let split = zero_weight.edge_betweenness();
+        assert!((split[&(VertexId('a'), VertexId('b'))] - 0.5).abs() < 1e-9);
+        assert!((split[&(VertexId('a'), VertexId('c'))] - 1.5).abs() < 1e-9);
+        assert!((split[&(VertexId('c'), VertexId('b'))] - 1.5).abs() < 1e-9);
+
+        // c reaches b and d two ways each through the zero-weight cycle.
+        let cyclic = Graph::try_from_edges(&[('c', 'd', 0), ('c', 'b', 0), ('b', 'd', 0), ('d', 'b', 0)]).unwrap();
+        let split = cyclic.edge_betweenness();
+        for (from, to, share) in [('c', 'b', 1.0), ('c', 'd', 1.0), ('b', 'd', 1.5), ('d', 'b', 1.5)] {
+            assert!((split[&(VertexId(from), VertexId(to))] - share).abs() < 1e-9, "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn edge_betweenness_matches_brute_force_with_zero_weights() {
+        for graph in zero_weight_graphs() {
+            let mut expected: HashMap<(VertexId, VertexId), f64> = graph.edge_betweenness().into_keys().map(|edge| (edge, 0.0)).collect();
+
+            for &source in graph.vertices.keys() {
+                for &target in graph.vertices.keys().filter(|&&target| target != source) {
+                    let paths = brute_force_shortest_paths(&graph, source, target);
+                    for path in &paths {
+                        for pair in path.windows(2) {
+                            *expected.get_mut(&(pair[0], pair[1])).unwrap() += 1.0 / paths.len() as f64;
+                        }
+                    }
+                }
+            }
+
+            for (edge, value) in graph.edge_betweenness() {
+                assert!((value - expected[&edge]).abs() < 1e-9, "{:?}: {} vs {}", edge, value, expected[&edge]);
+            }
+        }
+    }
+
+    #[test]
+    fn restore_undoes_changes_since_snapshot() {
+        let original = sample_graph();
+        let mut graph = sample_graph();
+        let snapshot = graph.snapshot();
+
+        graph.add_edge(VertexId('a'), VertexId('e'), 1);
+        graph.remove_vertex(VertexId('c'));
+        assert_eq!(graph.dijkstra(VertexId('a'))[&VertexId('e')], 1);
+
+        graph.restore(snapshot);
+        assert_eq!(graph, original);
+    }
+
+    #[test]
+    fn edge_disjoint_paths_reroutes_around_greedy_choice() {
+        // The cheapest single path s-x-y-t blocks both disjoint routes.
+        let graph = Graph::try_from_edges(&[
+            ('s', 'x', 1), ('x', 'y', 1), ('y', 't', 1), ('s', 'y', 3), ('x', 't', 3)
+        ]).unwrap();
+        let (s, t) = (VertexId('s'), VertexId('t'));
+
+        let mut paths = graph.edge_disjoint_paths(s, t, 5);
+        paths.sort();
+
+        assert_eq!(paths, vec![
+            vec![s, VertexId('x'), t],
+            vec![s, VertexId('y'), t]
+        ]);
+
+        let mut used = HashSet::new();
+        for path in &paths {
+            for pair in path.windows(2) {
+                assert!(used.insert((pair[0], pair[1])));
+                assert!(graph.edge_weight(pair[0], pair[1]).is_some());
+            }
+        }
+
+        assert_eq!(graph.edge_disjoint_paths(s, t, 1), vec![vec![s, VertexId('x'), VertexId('y'), t]]);
+        assert!(graph.edge_disjoint_paths(t, s, 2).is_empty());
+    }
+
+    #[test]
+    fn longest_path_dag_differs_from_shortest() {
+        let graph = sample_graph();
+
+        assert_eq!(
+            graph.longest_path_dag(VertexId('a'), VertexId('e')),
+            Ok(Some((11, vec![VertexId('a'), VertexId('b'), VertexId('e')])))
+        );
+        assert_eq!(graph.shortest_path_with_cost(VertexId('a'), VertexId('e')).unwrap().0, 7);
+        assert_eq!(graph.longest_path_dag(VertexId('e'), VertexId('a')), Ok(None));
+        assert_eq!(
+            graph.topological_sort(),
+            Ok(vec![VertexId('a'), VertexId('c'), VertexId('b'), VertexId('d'), VertexId('e')])
+        );
+
+        let cyclic = Graph::try_from_edges(&[('a', 'b', 1), ('b', 'a', 1)]).unwrap();
+        assert_eq!(cyclic.longest_path_dag(VertexId('a'), VertexId('b')), Err(GraphError::NotADag));
+    }
+
+    #[test]
+    fn dijkstra_with_weight_fn_overrides_stored_weights() {
+        let graph = sample_graph();
+        let plain = graph.dijkstra(VertexId('a'));
+
+        assert_eq!(graph.dijkstra_with_weight_fn(VertexId('a'), |_, _, w| w), plain);
+
+        let doubled = graph.dijkstra_with_weight_fn(VertexId('a'), |_, _, w| 2 * w);
+        for (vertex, distance) in &plain {
+            assert_eq!(doubled[vertex], 2 * distance);
+        }
+
+        // Closing c -> b to traffic reroutes through a -> b.
+        let congested = graph.dijkstra_with_weight_fn(VertexId('a'), |from, to, w| {
+            if (from, to) == (VertexId('c'), VertexId('b')) { 100 } else { w }
+        });
+        assert_eq!(congested[&VertexId('b')], 4);
+    }
+
+    #[test]
+    fn remove_edge_reports_missing_edges() {
+        let mut graph = sample_graph();
+        graph.add_edge(VertexId('a'), VertexId('b'), 9);
+
+        assert_eq!(graph.remove_edge(VertexId('a'), VertexId('b')), Ok(()));
+        assert_eq!(graph.edge_weight(VertexId('a'), VertexId('b')), None);
+        assert_eq!(
+            graph.remove_edge(VertexId('a'), VertexId('b')),
+            Err(GraphError::EdgeNotFound { from: VertexId('a'), to: VertexId('b') })
+        );
+        assert_eq!(graph.remove_edge(VertexId('z'), VertexId('a')), Err(GraphError::VertexNotFound(VertexId('z'))));
+    }
+
+    #[test]
+    fn graph_error_display_messages() {
+        let (a, b) = (VertexId('a'), VertexId('b'));
+        let cases = [
+            (GraphError::VertexNotFound(a), "vertex a not found"),
+            (GraphError::EdgeNotFound { from: a, to: b }, "edge a -> b not found"),
+            (GraphError::KeyMismatch { key: a, id: b }, "vertex b is stored under key a"),
+            (GraphError::ConflictingEdge { from: a, to: b }, "edge a -> b is given conflicting weights"),
+            (GraphError::NegativeWeight { from: a, to: b }, "edge a -> b has a negative weight"),
+            (GraphError::NegativeCycle, "graph contains a negative-weight cycle"),
+            (GraphError::NotADag, "graph contains a cycle"),
+            (GraphError::BlockedEndpoint(VertexId('a')), "endpoint a is blocked"),
+            (GraphError::Parse { line: 3, message: "bad arc".to_string() }, "parse error on line 3: bad arc")
+        ];
+
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+
+            let boxed: Box<dyn std::error::Error> = Box::new(error);
+            assert_eq!(boxed.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn astar_context_reuses_buffers_across_searches() {
+        let (graph, coordinates) = Graph::grid(8, 8, 2);
+        let manhattan = |goal: VertexId| {
+            let (gx, gy) = coordinates[&goal];
+            let coordinates = &coordinates;
+            move |v: VertexId| {
+                let (x, y) = coordinates[&v];
+                (2 * (x.abs_diff(gx) + y.abs_diff(gy))) as u32
+            }
+        };
+
+        let queries: Vec<(VertexId, VertexId)> = graph.sorted_ids().chunks(2).map(|c| (c[0], c[1])).collect();
+        let mut context = AStarContext::new();
+
+        for &(start, goal) in &queries {
+            assert_eq!(context.search(&graph, start, goal, manhattan(goal)), graph.astar(start, goal, manhattan(goal)));
+        }
+
+        let capacities = (context.open.capacity(), context.g_score.capacity(), context.came_from.capacity());
+
+        for &(start, goal) in queries.iter().rev() {
+            assert_eq!(context.search(&graph, start, goal, manhattan(goal)), graph.astar(start, goal, manhattan(goal)));
+            assert_eq!((context.open.capacity(), context.g_score.capacity(), context.came_from.capacity()), capacities);
+        }
+    }
+
+    #[test]
+    fn core_numbers_separate_dense_core_from_pendants() {
+        let mut graph = Graph::new();
+        let core = ['a', 'b', 'c', 'd'];
+        for (i, &from) in core.iter().enumerate() {
+            for &to in &core[i + 1..] {
+                graph.add_edge(VertexId(from), VertexId(to), 1);
+            }
+        }
+        graph.add_edges([
+            (VertexId('e'), VertexId('a'), 1), (VertexId('f'), VertexId('b'), 1),
+            (VertexId('g'), VertexId('f'), 1), (VertexId('h'), VertexId('h'), 1)
+        ]);
+
+        let cores = graph.core_numbers();
+
+        for id in core {
+            assert_eq!(cores[&VertexId(id)], 3);
+        }
+        for id in ['e', 'f', 'g'] {
+            assert_eq!(cores[&VertexId(id)], 1);
+        }
+        assert_eq!(cores[&VertexId('h')], 0);
+    }
+
+    #[test]
+    fn validate_reports_dangling_edge() {
+        let mut graph = Graph::new();
+        graph.add_vertex(Vertex { id: VertexId('a'), edges: vec![Edge { to: VertexId('z'), weight: 1 }] });
+
+        assert_eq!(graph.validate(), Err(GraphError::VertexNotFound(VertexId('z'))));
     }
 }